@@ -0,0 +1,233 @@
+use crate::documents::span_to_range;
+use crate::grammar;
+use crate::syntax::YaccSyntax;
+use cfgrammar::Spanned as _;
+use cfgrammar::{yacc, PIdx, Symbol};
+use lrlex::LexerDef as _;
+use lrpar::{LexError as _, Lexeme as _};
+use tower_lsp::lsp_types as lsp_ty;
+
+pub const SOURCE: &str = "nimbleparse_lsp";
+
+/// Diagnostics for every file touched by a build, a file mapped to an empty `Vec` has its diagnostics cleared.
+pub type Diagnostics = std::collections::HashMap<lsp_ty::Url, Vec<lsp_ty::Diagnostic>>;
+
+/// Converts an error from grmtools into a diagnostic at its first span.
+///
+/// Any further spans are given as related information, for duplication errors these
+/// are the locations of the duplicates.
+pub fn spanned_diagnostic<E>(
+    err: &E,
+    uri: &lsp_ty::Url,
+    rope: &ropey::Rope,
+    severity: lsp_ty::DiagnosticSeverity,
+) -> lsp_ty::Diagnostic
+where
+    E: cfgrammar::Spanned + std::fmt::Display,
+{
+    let spans = err.spans();
+    let range = spans
+        .first()
+        .map_or_else(lsp_ty::Range::default, |span| span_to_range(rope, *span));
+    let related_message = if matches!(err.spanskind(), cfgrammar::SpansKind::DuplicationError) {
+        "duplicate"
+    } else {
+        "related"
+    };
+    let related_information = spans
+        .iter()
+        .skip(1)
+        .map(|span| lsp_ty::DiagnosticRelatedInformation {
+            location: lsp_ty::Location {
+                uri: uri.clone(),
+                range: span_to_range(rope, *span),
+            },
+            message: related_message.to_string(),
+        })
+        .collect::<Vec<_>>();
+    lsp_ty::Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some(SOURCE.to_string()),
+        message: err.to_string(),
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
+        ..Default::default()
+    }
+}
+
+/// Reports each conflict of the state table, on the production being reduced.
+///
+/// Conflicts are errors unless their number matches the grammar's `%expect` or `%expect-rr`.
+pub fn conflict_diagnostics(
+    grm: &yacc::YaccGrammar,
+    sgraph: &lrtable::StateGraph<u32>,
+    stable: &lrtable::StateTable<u32>,
+    syntax: &YaccSyntax,
+    uri: &lsp_ty::Url,
+    rope: &ropey::Rope,
+) -> Vec<lsp_ty::Diagnostic> {
+    let mut diags = Vec::new();
+    let conflicts = if let Some(conflicts) = stable.conflicts() {
+        conflicts
+    } else {
+        return diags;
+    };
+    let location = |pidx: PIdx<u32>| lsp_ty::Location {
+        uri: uri.clone(),
+        range: span_to_range(rope, grammar::prod_or_rule_span(grm, syntax, pidx)),
+    };
+    let severity = |expected: Option<usize>, found: usize| {
+        if expected.unwrap_or(0) == found {
+            lsp_ty::DiagnosticSeverity::WARNING
+        } else {
+            lsp_ty::DiagnosticSeverity::ERROR
+        }
+    };
+
+    let sr_severity = severity(grm.expect(), conflicts.sr_len());
+    for &(tidx, pidx, stidx) in conflicts.sr_conflicts() {
+        let tok = grm.token_name(tidx).unwrap_or("$");
+        let shifts = sgraph
+            .closed_state(stidx)
+            .items
+            .keys()
+            .filter(|(s_pidx, sidx)| {
+                grm.prod(*s_pidx).get(usize::from(*sidx)) == Some(&Symbol::Token(tidx))
+            })
+            .map(|(s_pidx, _)| *s_pidx)
+            .collect::<Vec<_>>();
+        let shift_strs = shifts
+            .iter()
+            .map(|s_pidx| grammar::pp_prod(grm, *s_pidx))
+            .collect::<Vec<_>>();
+        diags.push(lsp_ty::Diagnostic {
+            range: location(pidx).range,
+            severity: Some(sr_severity),
+            source: Some(SOURCE.to_string()),
+            code: Some(lsp_ty::NumberOrString::String("shift-reduce".to_string())),
+            message: format!(
+                "Shift/Reduce conflict in state {} on lookahead '{tok}': shift in {} or reduce {}",
+                usize::from(stidx),
+                shift_strs.join(", "),
+                grammar::pp_prod(grm, pidx)
+            ),
+            related_information: Some(
+                shifts
+                    .iter()
+                    .zip(shift_strs.iter())
+                    .map(|(s_pidx, s)| lsp_ty::DiagnosticRelatedInformation {
+                        location: location(*s_pidx),
+                        message: format!("shift '{tok}' in {s}"),
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        });
+    }
+
+    let rr_severity = severity(grm.expectrr(), conflicts.rr_len());
+    for &(pidx1, pidx2, stidx) in conflicts.rr_conflicts() {
+        // The lookaheads both reductions are valid for.
+        let state = sgraph.closed_state(stidx);
+        let reduce_ctx = |pidx: PIdx<u32>| {
+            state
+                .items
+                .iter()
+                .find(|((i_pidx, sidx), _)| {
+                    *i_pidx == pidx && usize::from(*sidx) == grm.prod(pidx).len()
+                })
+                .map(|(_, ctx)| ctx)
+        };
+        let lookaheads = match (reduce_ctx(pidx1), reduce_ctx(pidx2)) {
+            (Some(ctx1), Some(ctx2)) => grm
+                .iter_tidxs()
+                .filter(|tidx| {
+                    let i = usize::from(*tidx);
+                    ctx1.get(i) == Some(true) && ctx2.get(i) == Some(true)
+                })
+                .map(|tidx| format!("'{}'", grm.token_name(tidx).unwrap_or("$")))
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        diags.push(lsp_ty::Diagnostic {
+            range: location(pidx1).range,
+            severity: Some(rr_severity),
+            source: Some(SOURCE.to_string()),
+            code: Some(lsp_ty::NumberOrString::String("reduce-reduce".to_string())),
+            message: format!(
+                "Reduce/Reduce conflict in state {} on lookahead {}: reduce {} or reduce {}",
+                usize::from(stidx),
+                lookaheads.join(", "),
+                grammar::pp_prod(grm, pidx1),
+                grammar::pp_prod(grm, pidx2)
+            ),
+            related_information: Some(vec![lsp_ty::DiagnosticRelatedInformation {
+                location: location(pidx2),
+                message: format!("reduce {}", grammar::pp_prod(grm, pidx2)),
+            }]),
+            ..Default::default()
+        });
+    }
+    diags
+}
+
+pub fn state_table_diagnostic(
+    err: &lrtable::StateTableError<u32>,
+    grm: &yacc::YaccGrammar,
+    syntax: &YaccSyntax,
+    uri: &lsp_ty::Url,
+    rope: &ropey::Rope,
+) -> lsp_ty::Diagnostic {
+    lsp_ty::Diagnostic {
+        range: span_to_range(rope, grammar::prod_or_rule_span(grm, syntax, err.pidx)),
+        severity: Some(lsp_ty::DiagnosticSeverity::ERROR),
+        source: Some(SOURCE.to_string()),
+        message: err.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Lexes and parses `src`, returning the span and message of each lexing and parsing error.
+///
+/// When the parser uses error recovery the message includes the repair sequences found.
+pub fn parse_errors(
+    lexerdef: &crate::LexerDef,
+    grm: &yacc::YaccGrammar,
+    rtpb: &lrpar::RTParserBuilder<u32, lrlex::DefaultLexerTypes<u32>>,
+    src: &str,
+) -> Vec<(cfgrammar::Span, String)> {
+    let lexer = lexerdef.lexer(src);
+    let (_, errs) = rtpb.parse_generictree(&lexer);
+    errs.iter()
+        .map(|err| {
+            let span = match err {
+                lrpar::LexParseError::LexError(e) => e.span(),
+                lrpar::LexParseError::ParseError(e) => e.lexeme().span(),
+            };
+            (span, err.pp(&lexer, &|tidx| grm.token_epp(tidx)))
+        })
+        .collect()
+}
+
+/// Lexes and parses `rope`, reporting each lexing and parsing error.
+pub fn parse_diagnostics(
+    lexerdef: &crate::LexerDef,
+    grm: &yacc::YaccGrammar,
+    rtpb: &lrpar::RTParserBuilder<u32, lrlex::DefaultLexerTypes<u32>>,
+    rope: &ropey::Rope,
+) -> Vec<lsp_ty::Diagnostic> {
+    parse_errors(lexerdef, grm, rtpb, &rope.to_string())
+        .into_iter()
+        .map(|(span, message)| lsp_ty::Diagnostic {
+            range: span_to_range(rope, span),
+            severity: Some(lsp_ty::DiagnosticSeverity::ERROR),
+            source: Some(SOURCE.to_string()),
+            message,
+            ..Default::default()
+        })
+        .collect()
+}
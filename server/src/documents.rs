@@ -0,0 +1,151 @@
+use tower_lsp::lsp_types as lsp_ty;
+
+/// The editor's copy of a file which is currently open.
+///
+/// While a file is open its contents in the editor take precedence over whatever is on disk.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub rope: ropey::Rope,
+    pub version: i32,
+}
+
+impl Document {
+    pub fn new(text: &str, version: i32) -> Self {
+        Document {
+            rope: ropey::Rope::from_str(text),
+            version,
+        }
+    }
+
+    /// Applies a single change event, the range is absent when the client resends the whole text.
+    pub fn apply_change(&mut self, change: lsp_ty::TextDocumentContentChangeEvent) {
+        if let Some(range) = change.range {
+            let start = position_to_char(&self.rope, range.start);
+            let end = position_to_char(&self.rope, range.end).max(start);
+            self.rope.remove(start..end);
+            self.rope.insert(start, &change.text);
+        } else {
+            self.rope = ropey::Rope::from_str(&change.text);
+        }
+    }
+}
+
+// Positions sent over the wire are in utf-16 code units, while grmtools spans are byte offsets.
+
+/// Converts an lsp position to a char index, clamping positions past the end of a line or file.
+pub fn position_to_char(rope: &ropey::Rope, pos: lsp_ty::Position) -> usize {
+    let line = (pos.line as usize).min(rope.len_lines().saturating_sub(1));
+    let line_start = rope.line_to_char(line);
+    let line_slice = rope.line(line);
+    let mut line_len = line_slice.len_chars();
+    while line_len > 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
+        line_len -= 1;
+    }
+    let line_end = line_start + line_len;
+    let utf16_start = rope.char_to_utf16_cu(line_start);
+    let utf16_pos = (utf16_start + pos.character as usize).min(rope.len_utf16_cu());
+    rope.utf16_cu_to_char(utf16_pos).min(line_end)
+}
+
+pub fn position_to_byte(rope: &ropey::Rope, pos: lsp_ty::Position) -> usize {
+    rope.char_to_byte(position_to_char(rope, pos))
+}
+
+pub fn byte_to_position(rope: &ropey::Rope, byte: usize) -> lsp_ty::Position {
+    let char_idx = rope.byte_to_char(byte.min(rope.len_bytes()));
+    let line = rope.char_to_line(char_idx);
+    let line_start = rope.line_to_char(line);
+    let character = rope.char_to_utf16_cu(char_idx) - rope.char_to_utf16_cu(line_start);
+    lsp_ty::Position::new(line as u32, character as u32)
+}
+
+pub fn span_to_range(rope: &ropey::Rope, span: cfgrammar::Span) -> lsp_ty::Range {
+    lsp_ty::Range::new(
+        byte_to_position(rope, span.start()),
+        byte_to_position(rope, span.end()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> lsp_ty::TextDocumentContentChangeEvent {
+        lsp_ty::TextDocumentContentChangeEvent {
+            range: range.map(|((l1, c1), (l2, c2))| {
+                lsp_ty::Range::new(lsp_ty::Position::new(l1, c1), lsp_ty::Position::new(l2, c2))
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn apply_changes() {
+        let mut doc = Document::new("hello world\n", 1);
+        doc.apply_change(change(Some(((0, 6), (0, 11))), "there"));
+        assert_eq!(doc.rope.to_string(), "hello there\n");
+        doc.apply_change(change(Some(((1, 0), (1, 0))), "x"));
+        assert_eq!(doc.rope.to_string(), "hello there\nx");
+        doc.apply_change(change(Some(((0, 0), (0, 6))), ""));
+        assert_eq!(doc.rope.to_string(), "there\nx");
+        // An end before the start is treated as an insertion at the start.
+        doc.apply_change(change(Some(((1, 1), (0, 0))), "y"));
+        assert_eq!(doc.rope.to_string(), "there\nxy");
+        doc.apply_change(change(None, "new"));
+        assert_eq!(doc.rope.to_string(), "new");
+    }
+
+    #[test]
+    fn multibyte_and_astral() {
+        // 'é' is two bytes and one utf-16 code unit, '😀' four bytes and two code units.
+        let rope = ropey::Rope::from_str("aé😀b\n");
+        let at = |character| position_to_byte(&rope, lsp_ty::Position::new(0, character));
+        assert_eq!(at(1), 1);
+        assert_eq!(at(2), 3);
+        assert_eq!(at(4), 7);
+        assert_eq!(byte_to_position(&rope, 3), lsp_ty::Position::new(0, 2));
+        assert_eq!(byte_to_position(&rope, 7), lsp_ty::Position::new(0, 4));
+
+        let mut doc = Document::new("😀b", 1);
+        doc.apply_change(change(Some(((0, 2), (0, 2))), "x"));
+        assert_eq!(doc.rope.to_string(), "😀xb");
+    }
+
+    #[test]
+    fn crlf() {
+        let rope = ropey::Rope::from_str("a\r\nbc\r\n");
+        assert_eq!(position_to_byte(&rope, lsp_ty::Position::new(0, 1)), 1);
+        assert_eq!(position_to_byte(&rope, lsp_ty::Position::new(1, 1)), 4);
+        assert_eq!(byte_to_position(&rope, 4), lsp_ty::Position::new(1, 1));
+        assert_eq!(byte_to_position(&rope, 7), lsp_ty::Position::new(2, 0));
+    }
+
+    #[test]
+    fn past_the_end() {
+        let rope = ropey::Rope::from_str("a\r\nbc\r\n");
+        // Past the end of a line is its end, before the line break.
+        assert_eq!(position_to_byte(&rope, lsp_ty::Position::new(0, 5)), 1);
+        assert_eq!(position_to_byte(&rope, lsp_ty::Position::new(1, 100)), 5);
+        // Past the end of the file is the end of its last line.
+        assert_eq!(position_to_byte(&rope, lsp_ty::Position::new(10, 0)), 7);
+        assert_eq!(byte_to_position(&rope, 100), lsp_ty::Position::new(2, 0));
+
+        let mut doc = Document::new("ab", 1);
+        doc.apply_change(change(Some(((0, 9), (4, 0))), "c"));
+        assert_eq!(doc.rope.to_string(), "abc");
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = "aé\n😀b\n\nc";
+        let rope = ropey::Rope::from_str(src);
+        for byte in src.char_indices().map(|(byte, _)| byte).chain([src.len()]) {
+            let pos = byte_to_position(&rope, byte);
+            assert_eq!(position_to_byte(&rope, pos), byte, "{pos:?}");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Formatting of `.y` files.
+//!
+//! Only the whitespace between the items of a `YaccSyntax` is rewritten, so comments, action code
+//! and strings are kept byte for byte. A file containing text the scanner didn't recognise is left
+//! alone, rather than risk losing that text.
+//!
+//! Declarations are put one per line, and rules are laid out as below, separated by blank lines.
+//!
+//! ```text
+//! expr -> Type
+//!     : expr '+' term { ... }
+//!     | term
+//!     ;
+//! ```
+use crate::documents::{position_to_byte, span_to_range};
+use crate::syntax::{ItemKind, YaccSyntax};
+use cfgrammar::Span;
+use tower_lsp::lsp_types as lsp_ty;
+
+/// The indent of `:`, `|` and `;`.
+const INDENT: &str = "    ";
+/// The indent of lines continuing an alternative, which aligns them with the first symbol.
+const CONTINUATION: &str = "      ";
+
+/// A line break, keeping a single blank line where the original had any.
+fn line_break(gap: &str) -> String {
+    if gap.matches('\n').count() > 1 {
+        "\n\n".to_string()
+    } else {
+        "\n".to_string()
+    }
+}
+
+/// The whitespace that belongs before and between each item of `syntax`.
+///
+/// Returns `None` when anything other than whitespace lies between two items.
+fn gaps(src: &str, syntax: &YaccSyntax) -> Option<Vec<(Span, String)>> {
+    let items = &syntax.items;
+    let mut gaps = Vec::with_capacity(items.len() + 1);
+    if let Some(first) = items.first() {
+        if !src[..first.span.start()].trim().is_empty() {
+            return None;
+        }
+        gaps.push((Span::new(0, first.span.start()), String::new()));
+    }
+    let mut sections = 0;
+    let mut in_rule = false;
+    for (prev, next) in items.iter().zip(items.iter().skip(1)) {
+        let span = Span::new(prev.span.end(), next.span.start());
+        let gap = &src[span.start()..span.end()];
+        if !gap.trim().is_empty() {
+            return None;
+        }
+        match prev.kind {
+            ItemKind::SectionMarker => sections += 1,
+            ItemKind::RuleName => in_rule = true,
+            ItemKind::Punct if src.as_bytes()[prev.span.start()] == b';' => in_rule = false,
+            _ => (),
+        }
+        let after_line_comment =
+            prev.kind == ItemKind::Comment && src[prev.span.start()..].starts_with("//");
+        let same_line = !gap.contains('\n');
+        let whitespace = match next.kind {
+            // The programs section is code, which is kept as it is.
+            _ if sections > 1 => gap.to_string(),
+            ItemKind::SectionMarker | ItemKind::Directive if sections == 0 => line_break(gap),
+            // Rules directly following one another are separated by a blank line.
+            ItemKind::RuleName if prev.kind == ItemKind::Punct => "\n\n".to_string(),
+            ItemKind::SectionMarker | ItemKind::RuleName => line_break(gap),
+            ItemKind::Comment if same_line && !after_line_comment => " ".to_string(),
+            ItemKind::Comment if in_rule => format!("\n{CONTINUATION}"),
+            ItemKind::Comment => line_break(gap),
+            ItemKind::Punct => format!("\n{INDENT}"),
+            _ if after_line_comment && in_rule => format!("\n{CONTINUATION}"),
+            _ if after_line_comment => format!("\n{INDENT}"),
+            _ => " ".to_string(),
+        };
+        gaps.push((span, whitespace));
+    }
+    if let Some(last) = items.last() {
+        let gap = &src[last.span.end()..];
+        if !gap.trim().is_empty() {
+            return None;
+        }
+        if sections < 2 {
+            gaps.push((Span::new(last.span.end(), src.len()), "\n".to_string()));
+        }
+    }
+    Some(gaps)
+}
+
+/// The edits formatting the `.y` file `rope`, limited to those touching `range` if given.
+pub fn format_edits(
+    rope: &ropey::Rope,
+    syntax: &YaccSyntax,
+    range: Option<lsp_ty::Range>,
+) -> Option<Vec<lsp_ty::TextEdit>> {
+    let src = rope.to_string();
+    let range = range.map(|range| {
+        (
+            position_to_byte(rope, range.start),
+            position_to_byte(rope, range.end),
+        )
+    });
+    let edits = gaps(&src, syntax)?
+        .into_iter()
+        .filter(|(span, _)| {
+            range.map_or(true, |(start, end)| {
+                span.start() <= end && start <= span.end()
+            })
+        })
+        .filter(|(span, whitespace)| src[span.start()..span.end()] != *whitespace)
+        .map(|(span, new_text)| lsp_ty::TextEdit {
+            range: span_to_range(rope, span),
+            new_text,
+        })
+        .collect();
+    Some(edits)
+}
+
+/// Indents the line at `pos` when the previous line ends with the `:` or `|` of a rule.
+pub fn on_type_edits(
+    rope: &ropey::Rope,
+    syntax: &YaccSyntax,
+    pos: lsp_ty::Position,
+) -> Vec<lsp_ty::TextEdit> {
+    if pos.line == 0 || pos.line as usize >= rope.len_lines() {
+        return Vec::new();
+    }
+    let prev_line = rope.line(pos.line as usize - 1).to_string();
+    let prev_start = rope.line_to_byte(pos.line as usize - 1);
+    let prev_end = prev_start + prev_line.trim_end().len();
+    let ends_with_punct = prev_end > prev_start
+        && syntax.items.iter().any(|item| {
+            item.kind == ItemKind::Punct
+                && item.span.end() == prev_end
+                && matches!(rope.byte(item.span.start()), b':' | b'|')
+        });
+    if !ends_with_punct {
+        return Vec::new();
+    }
+    let line = rope.line(pos.line as usize).to_string();
+    let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+    if &line[..indent] == CONTINUATION {
+        return Vec::new();
+    }
+    vec![lsp_ty::TextEdit {
+        range: lsp_ty::Range::new(
+            lsp_ty::Position::new(pos.line, 0),
+            lsp_ty::Position::new(pos.line, indent as u32),
+        ),
+        new_text: CONTINUATION.to_string(),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies every gap of `src`, as `format_edits` would without a range.
+    fn format(src: &str) -> Option<String> {
+        let syntax = YaccSyntax::new(src);
+        let mut out = String::new();
+        let mut pos = 0;
+        for (span, whitespace) in gaps(src, &syntax)? {
+            out.push_str(&src[pos..span.start()]);
+            out.push_str(&whitespace);
+            pos = span.end();
+        }
+        out.push_str(&src[pos..]);
+        Some(out)
+    }
+
+    fn assert_formats(src: &str, expected: &str) {
+        let formatted = format(src).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn idempotent() {
+        let src = "%start expr %token INT\n%%\nexpr: expr '+' term | term; term: INT;\n";
+        assert_formats(
+            src,
+            "%start expr\n%token INT\n%%\nexpr\n    : expr '+' term\n    | term\n    ;\n\nterm\n    : INT\n    ;\n",
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_formats(
+            "%token a // the a\n/* b */ %token b\n%%\nr: a /* x */ b // y\n | b ;\n",
+            "%token a // the a\n/* b */\n%token b\n%%\nr\n    : a /* x */ b // y\n    | b\n    ;\n",
+        );
+    }
+
+    #[test]
+    fn actions() {
+        let action = "{\n        let c = '{';\n        let s = \"}\";\n        fn f<'a>(x: &'a str) -> &'a str { x }\n    }";
+        let src = format!("%%\nr -> &'static str: 'a'   {action} ;\n");
+        let formatted = format(&src).unwrap();
+        assert_eq!(
+            formatted,
+            format!("%%\nr -> &'static str\n    : 'a' {action}\n    ;\n")
+        );
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn prec_type_and_missing_semicolon() {
+        assert_formats(
+            "%%\ne -> u64: '-' e %prec UMINUS | INT\nt: INT;\n",
+            "%%\ne -> u64\n    : '-' e %prec UMINUS\n    | INT\nt\n    : INT\n    ;\n",
+        );
+    }
+
+    #[test]
+    fn unrecognised_text() {
+        assert_eq!(format("%token a\n@@\n%%\nr: a;\n"), None);
+        assert_eq!(format("%%\nr: a;\n#\n"), None);
+    }
+}
@@ -0,0 +1,50 @@
+//! Helpers for presenting parts of a `YaccGrammar` to the user.
+use crate::syntax::YaccSyntax;
+use cfgrammar::{yacc::YaccGrammar, PIdx, RIdx, Span, Symbol};
+
+pub fn symbol_name(grm: &YaccGrammar, sym: Symbol<u32>) -> &str {
+    match sym {
+        Symbol::Rule(ridx) => grm.rule_name_str(ridx),
+        Symbol::Token(tidx) => grm.token_name(tidx).unwrap_or("$"),
+    }
+}
+
+/// The rules a user wrote, excluding those the grammar adds itself.
+pub fn user_rules(grm: &YaccGrammar) -> impl Iterator<Item = RIdx<u32>> + '_ {
+    grm.iter_rules()
+        .filter(move |ridx| *ridx != grm.start_rule_idx() && Some(*ridx) != grm.implicit_rule())
+}
+
+/// Formats a production as `rule: sym1 sym2`.
+pub fn pp_prod(grm: &YaccGrammar, pidx: PIdx<u32>) -> String {
+    let mut s = format!("{}:", grm.rule_name_str(grm.prod_to_rule(pidx)));
+    for sym in grm.prod(pidx) {
+        s.push(' ');
+        match sym {
+            Symbol::Rule(_) => s.push_str(symbol_name(grm, *sym)),
+            Symbol::Token(_) => {
+                s.push('\'');
+                s.push_str(symbol_name(grm, *sym));
+                s.push('\'');
+            }
+        }
+    }
+    s
+}
+
+/// Finds the source of production `pidx`, returning `None` for productions the grammar adds itself.
+pub fn prod_span(grm: &YaccGrammar, syntax: &YaccSyntax, pidx: PIdx<u32>) -> Option<Span> {
+    let ridx = grm.prod_to_rule(pidx);
+    let nth = grm.rule_to_prods(ridx).iter().position(|p| *p == pidx)?;
+    syntax
+        .alts_of(grm.rule_name_str(ridx))
+        .nth(nth)
+        .map(|alt| alt.span)
+}
+
+/// Like `prod_span`, but falls back to the rule name for empty or generated productions.
+pub fn prod_or_rule_span(grm: &YaccGrammar, syntax: &YaccSyntax, pidx: PIdx<u32>) -> Span {
+    prod_span(grm, syntax, pidx)
+        .filter(|span| span.start() < span.end())
+        .unwrap_or_else(|| grm.rule_name_span(grm.prod_to_rule(pidx)))
+}
@@ -0,0 +1,180 @@
+//! Warnings about symbols of a grammar which can never take part in a parse.
+//!
+//! Each lint has a stable diagnostic code. Listing a symbol in `%expect-unused` silences every
+//! lint about that symbol, in the same way it silences grmtools' own unused symbol warnings.
+use crate::diagnostics::SOURCE;
+use crate::documents::span_to_range;
+use crate::grammar::user_rules;
+use crate::syntax::{SymbolRole, YaccSyntax};
+use cfgrammar::{yacc::YaccGrammar, Span, Symbol};
+use std::collections::HashSet;
+use tower_lsp::lsp_types as lsp_ty;
+
+/// A token with a rule in the `.l` file which the `.y` file never uses.
+pub const UNUSED_TOKEN: &str = "unused-token";
+/// A token used in the `.y` file which no rule of the `.l` file produces.
+pub const MISSING_LEXER_RULE: &str = "missing-lexer-rule";
+/// A rule which can't be reached from the start rule.
+pub const UNREACHABLE_RULE: &str = "unreachable-rule";
+/// A rule which can never derive a string made only of tokens.
+pub const UNPRODUCTIVE_RULE: &str = "unproductive-rule";
+
+/// The symbols listed by `%expect-unused` declarations.
+fn expected_unused(syntax: &YaccSyntax) -> HashSet<&str> {
+    syntax
+        .symbols
+        .iter()
+        .filter(|sym| match sym.role {
+            SymbolRole::Decl(decl) => syntax.decls[decl].directive == "expect-unused",
+            _ => false,
+        })
+        .map(|sym| sym.name.as_str())
+        .collect()
+}
+
+fn lint(
+    code: &str,
+    message: String,
+    range: lsp_ty::Range,
+    unnecessary: bool,
+) -> lsp_ty::Diagnostic {
+    lsp_ty::Diagnostic {
+        range,
+        severity: Some(lsp_ty::DiagnosticSeverity::WARNING),
+        code: Some(lsp_ty::NumberOrString::String(code.to_string())),
+        source: Some(SOURCE.to_string()),
+        message,
+        tags: unnecessary.then(|| vec![lsp_ty::DiagnosticTag::UNNECESSARY]),
+        ..Default::default()
+    }
+}
+
+/// Reports rules unreachable from `%start`, and rules which can never derive a string of tokens.
+pub fn rule_lints(
+    grm: &YaccGrammar,
+    syntax: &YaccSyntax,
+    rope: &ropey::Rope,
+) -> Vec<lsp_ty::Diagnostic> {
+    let expected = expected_unused(syntax);
+    let rules_len = usize::from(grm.rules_len());
+
+    let mut reachable = vec![false; rules_len];
+    let mut todo = vec![grm.start_rule_idx()];
+    reachable[usize::from(grm.start_rule_idx())] = true;
+    while let Some(ridx) = todo.pop() {
+        for pidx in grm.rule_to_prods(ridx) {
+            for sym in grm.prod(*pidx) {
+                if let Symbol::Rule(callee) = sym {
+                    if !reachable[usize::from(*callee)] {
+                        reachable[usize::from(*callee)] = true;
+                        todo.push(*callee);
+                    }
+                }
+            }
+        }
+    }
+
+    // A rule is productive once one of its productions contains only tokens and productive rules.
+    let mut productive = vec![false; rules_len];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for ridx in grm.iter_rules() {
+            if productive[usize::from(ridx)] {
+                continue;
+            }
+            let derives = grm.rule_to_prods(ridx).iter().any(|pidx| {
+                grm.prod(*pidx).iter().all(|sym| match sym {
+                    Symbol::Token(_) => true,
+                    Symbol::Rule(callee) => productive[usize::from(*callee)],
+                })
+            });
+            if derives {
+                productive[usize::from(ridx)] = true;
+                changed = true;
+            }
+        }
+    }
+
+    // The start rule grmtools adds derives just the user's start rule.
+    let start = grm
+        .rule_to_prods(grm.start_rule_idx())
+        .iter()
+        .flat_map(|pidx| grm.prod(*pidx))
+        .find_map(|sym| match sym {
+            Symbol::Rule(ridx) => Some(grm.rule_name_str(*ridx)),
+            Symbol::Token(_) => None,
+        })
+        .unwrap_or("");
+    let mut diags = Vec::new();
+    for ridx in user_rules(grm) {
+        let name = grm.rule_name_str(ridx);
+        if expected.contains(name) {
+            continue;
+        }
+        let range = span_to_range(rope, grm.rule_name_span(ridx));
+        if !reachable[usize::from(ridx)] {
+            diags.push(lint(
+                UNREACHABLE_RULE,
+                format!("Rule '{name}' is unreachable from the start rule '{start}'"),
+                range,
+                true,
+            ));
+        }
+        if !productive[usize::from(ridx)] {
+            diags.push(lint(
+                UNPRODUCTIVE_RULE,
+                format!("Rule '{name}' can never derive a string of tokens"),
+                range,
+                false,
+            ));
+        }
+    }
+    diags
+}
+
+/// Reports tokens the lexer defines but the grammar never uses, on their rules in the `.l` file,
+/// and tokens the grammar uses but the lexer never produces, on their first use in the `.y` file.
+///
+/// The sets are those returned by `LexerDef::set_rule_ids`.
+pub fn token_lints(
+    syntax: &YaccSyntax,
+    y_rope: &ropey::Rope,
+    l_rope: &ropey::Rope,
+    missing_from_lexer: &[String],
+    missing_from_parser: &[(String, Span)],
+) -> (Vec<lsp_ty::Diagnostic>, Vec<lsp_ty::Diagnostic>) {
+    let expected = expected_unused(syntax);
+    let mut y_diags = Vec::new();
+    for name in missing_from_lexer {
+        if expected.contains(name.as_str()) {
+            continue;
+        }
+        let first_use = syntax
+            .symbols
+            .iter()
+            .filter(|sym| sym.name == *name)
+            .min_by_key(|sym| !matches!(sym.role, SymbolRole::Production { .. }));
+        if let Some(sym) = first_use {
+            y_diags.push(lint(
+                MISSING_LEXER_RULE,
+                format!("Token '{name}' is used in the grammar but no lexer rule produces it"),
+                span_to_range(y_rope, sym.name_span()),
+                false,
+            ));
+        }
+    }
+    let mut l_diags = Vec::new();
+    for (name, span) in missing_from_parser {
+        if expected.contains(name.as_str()) {
+            continue;
+        }
+        l_diags.push(lint(
+            UNUSED_TOKEN,
+            format!("Token '{name}' is defined in the lexer but never used in the grammar"),
+            span_to_range(l_rope, *span),
+            true,
+        ));
+    }
+    (y_diags, l_diags)
+}
@@ -1,3 +1,16 @@
+mod diagnostics;
+mod documents;
+mod format;
+mod grammar;
+mod lints;
+mod pretty;
+mod railroad;
+mod repairs;
+mod semantic_tokens;
+mod symbols;
+mod syntax;
+mod testsuite;
+
 use cfgrammar::yacc;
 use lrpar::RTParserBuilder;
 use ouroboros::self_referencing;
@@ -30,9 +43,11 @@ pub enum StateGraphPretty {
     AllEdges,
 }
 
+#[derive(Clone)]
 struct Backend {
     client: Client,
-    state: tokio::sync::Mutex<State>,
+    /// Shared with the tasks which run generators in the background.
+    state: std::sync::Arc<tokio::sync::Mutex<State>>,
 }
 
 #[derive(Debug, Clone)]
@@ -47,16 +62,342 @@ struct ServerDocumentParams {
 }
 
 impl Backend {
+    /// Rebuilds every parser whose grammar or lexer is `path` and reparses its open inputs,
+    /// or reparses `path` when it is an input. Then publishes the resulting diagnostics.
+    async fn file_changed(&self, path: &std::path::Path) {
+        let mut state = self.state.lock().await;
+        let mut ids = Vec::new();
+        let mut workspaces = Vec::new();
+        state.affected_parsers(path, &mut ids);
+        for id in ids {
+            let parser_info = if let Some(parser_info) = state.parser_info(id) {
+                parser_info.clone()
+            } else {
+                continue;
+            };
+            let (mut diags, inputs) = if parser_info.is_parser(path) || parser_info.is_lexer(path) {
+                (state.build_parser(id), state.open_inputs(&parser_info))
+            } else {
+                (diagnostics::Diagnostics::new(), vec![path.to_path_buf()])
+            };
+            for input in inputs {
+                if let Some((uri, input_diags)) = state.parse_file(id, &input) {
+                    diags.insert(uri, input_diags);
+                }
+            }
+            // Tests re-run when their parser changes.
+            if parser_info.is_parser(path) || parser_info.is_lexer(path) {
+                workspaces.extend(state.workspace_of(&parser_info));
+            }
+            self.publish_diagnostics(&state, diags).await;
+        }
+        workspaces.sort();
+        workspaces.dedup();
+        for workspace_path in &workspaces {
+            let diags = state.run_tests(workspace_path);
+            self.publish_diagnostics(&state, diags).await;
+        }
+        // Otherwise only an edited test file re-runs, inputs files needn't have the extension of a
+        // parser.
+        let rerun = state
+            .test_workspace_of(path)
+            .map_or(false, |(workspace_path, _)| {
+                !workspaces.contains(workspace_path)
+            });
+        if rerun {
+            let diags = state.rerun_test_file(path);
+            self.publish_diagnostics(&state, diags).await;
+        }
+    }
+
+    /// Registers file watchers for the inputs of every parser and each `nimbleparse.toml`.
+    async fn register_watchers(&self, state: &State) -> jsonrpc::Result<()> {
+        let registration = state.watchers();
+        self.client
+            .log_message(
+                lsp_ty::MessageType::LOG,
+                format!("registering! {registration:?}"),
+            )
+            .await;
+        let result = self.client.register_capability(vec![registration]).await;
+        if let Err(e) = &result {
+            self.client
+                .log_message(
+                    lsp_ty::MessageType::ERROR,
+                    format!(
+                        "registering for {}: {}",
+                        "workspace/didChangeWatchedFiles", e
+                    ),
+                )
+                .await;
+        }
+        result
+    }
+
+    /// Rereads the `nimbleparse.toml` of a workspace, then replaces the parsers and tests it
+    /// configured with the new ones. A file which fails to read or deserialise is reported,
+    /// and the workspace keeps its previous configuration.
+    async fn reload_workspace(&self, workspace_path: &std::path::Path) {
+        let workspace = match read_workspace(workspace_path).await {
+            Ok(workspace) => workspace,
+            Err(e) => {
+                self.client
+                    .show_message(lsp_ty::MessageType::ERROR, format!("Not reloaded: {e}"))
+                    .await;
+                return;
+            }
+        };
+        let mut state_guard = self.state.lock().await;
+        let state = state_guard.deref_mut();
+        let removed = state
+            .extensions
+            .values()
+            .filter(|parser_info| {
+                state.workspace_of(parser_info).as_deref() == Some(workspace_path)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        // Everything the old configuration published diagnostics for, which is cleared unless
+        // the new configuration publishes it again.
+        let mut stale = state.test_files(workspace_path);
+        for parser_info in &removed {
+            stale.push(parser_info.l_path.clone());
+            stale.push(parser_info.y_path.clone());
+            stale.extend(state.open_inputs(parser_info));
+            state.extensions.remove(&parser_info.extension);
+        }
+        state
+            .toml
+            .insert(workspace_path.to_path_buf(), WorkspaceCfg { workspace });
+        // The ids of removed parsers are reused, so `parsing_state` doesn't grow on each reload.
+        let mut free = removed.iter().map(ParserInfo::id).collect::<Vec<_>>();
+        let ids = state.insert_parsers(workspace_path, &mut free);
+        for id in free {
+            if let Some(parsing_state) = state.parsing_state.get_mut(id) {
+                *parsing_state = ParsingState::new(ParserData(None), |_| None);
+            }
+        }
+
+        let mut diags = diagnostics::Diagnostics::new();
+        for id in ids {
+            diags.extend(state.build_parser(id));
+            let inputs = state
+                .parser_info(id)
+                .map(|parser_info| state.open_inputs(parser_info))
+                .unwrap_or_default();
+            for input in inputs {
+                diags.extend(state.parse_file(id, &input));
+            }
+        }
+        diags.extend(state.run_tests(workspace_path));
+        for path in stale {
+            let uri = if let Ok(uri) = lsp_ty::Url::from_file_path(&path) {
+                uri
+            } else {
+                continue;
+            };
+            if diags.contains_key(&uri) {
+                continue;
+            }
+            // An open file which is no longer a test is still an input of its parser.
+            let reparsed = state
+                .parser_for(&path)
+                .filter(|_| state.documents.contains_key(&path))
+                .and_then(|parser_info| state.parse_file(parser_info.id(), &path));
+            diags.extend(reparsed);
+            diags.entry(uri).or_default();
+        }
+        self.publish_diagnostics(state, diags).await;
+
+        if state.client_monitor {
+            let unregister = self
+                .client
+                .unregister_capability(vec![lsp_ty::Unregistration {
+                    id: WATCHERS_ID.to_string(),
+                    method: "workspace/didChangeWatchedFiles".to_string(),
+                }])
+                .await;
+            match unregister {
+                // Failures are logged by `register_watchers`.
+                Ok(()) => {
+                    let _ = self.register_watchers(state).await;
+                }
+                Err(e) => {
+                    self.client
+                        .log_message(
+                            lsp_ty::MessageType::ERROR,
+                            format!(
+                                "unregistering for {}: {}",
+                                "workspace/didChangeWatchedFiles", e
+                            ),
+                        )
+                        .await;
+                }
+            }
+        }
+        drop(state_guard);
+        // Only generators whose configuration changed are run again.
+        self.run_generators(workspace_path, false).await;
+    }
+
+    /// Starts the generators of a workspace whose inputs aren't cached, or all of them with
+    /// `regenerate`. Once they finish the workspace's tests rerun with the new inputs, and their
+    /// diagnostics are published.
+    ///
+    /// Generators run in a task of their own without the state locked, so neither the handler
+    /// which started them nor any other request waits on them.
+    async fn run_generators(&self, workspace_path: &std::path::Path, regenerate: bool) {
+        let state = self.state.lock().await;
+        let generators = state.generators(workspace_path);
+        let cached = state.generated.get(workspace_path);
+        let mut pending = generators
+            .iter()
+            .filter(|generator| {
+                regenerate
+                    || !cached.map_or(false, |cached| {
+                        cached.iter().any(|(cached, _)| cached == *generator)
+                    })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        pending.dedup();
+        drop(state);
+        if pending.is_empty() {
+            return;
+        }
+
+        let backend = self.clone();
+        let workspace_path = workspace_path.to_path_buf();
+        tokio::spawn(async move {
+            let mut results = Vec::with_capacity(pending.len());
+            for generator in pending {
+                let generated = testsuite::generate(&generator, &workspace_path).await;
+                results.push((generator, generated));
+            }
+
+            let mut state = backend.state.lock().await;
+            // The workspace may have been reloaded while the generators ran.
+            let generators = state.generators(&workspace_path);
+            let cached = state.generated.entry(workspace_path.clone()).or_default();
+            // Forget the inputs of generators which have since been removed or changed.
+            cached.retain(|(cached, _)| generators.contains(cached));
+            for (generator, generated) in results {
+                if generators.contains(&generator) {
+                    cached.retain(|(cached, _)| *cached != generator);
+                    cached.push((generator, generated));
+                }
+            }
+            let diags = state.run_tests(&workspace_path);
+            backend.publish_diagnostics(&state, diags).await;
+        });
+    }
+
+    async fn publish_diagnostics(&self, state: &State, diags: diagnostics::Diagnostics) {
+        for (uri, diags) in diags {
+            let version = uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| state.documents.get(&path))
+                .map(|doc| doc.version);
+            self.client.publish_diagnostics(uri, diags, version).await;
+        }
+    }
+
     async fn get_server_document(
         &self,
         params: ServerDocumentParams,
     ) -> jsonrpc::Result<Option<String>> {
-        let state = self.state.lock().await;
+        let mut state = self.state.lock().await;
         if params.cmd == "generictree.cmd" {
             let path = std::path::PathBuf::from(&params.path);
             let parser_info = state.parser_for(&path);
-            // FIXME
-            Ok(None)
+            if let Some(parser_info) = parser_info {
+                let rope = state.file_contents(&path).map_err(|e| jsonrpc::Error {
+                    code: jsonrpc::ErrorCode::InvalidParams,
+                    message: Cow::from(format!("Unable to read file: {e}")),
+                    data: Some(serde_json::Value::String(params.path.clone())),
+                })?;
+                Ok(state
+                    .parsing_state
+                    .get(parser_info.id())
+                    .and_then(ParsingState::built)
+                    .map(|parser| pretty::generic_tree(&parser, &rope)))
+            } else {
+                Ok(None)
+            }
+        } else if params.cmd == "update_snapshot.cmd" {
+            // Writes the parse tree of a test to its snapshot, returning the snapshot's path.
+            let path = std::path::PathBuf::from(&params.path);
+            let error = |code, message: String| jsonrpc::Error {
+                code,
+                message: Cow::from(message),
+                data: Some(serde_json::Value::String(params.path.clone())),
+            };
+            let has_snapshots = state.test_of(&path).map_or(false, |test| {
+                test.snapshots && matches!(test.kind.get_ref(), nimbleparse_toml::TestKind::Dir(_))
+            });
+            if !has_snapshots {
+                return Err(error(
+                    jsonrpc::ErrorCode::InvalidParams,
+                    "Not in a test directory with snapshots".to_string(),
+                ));
+            }
+            let tree = state.parser_for(&path).and_then(|parser_info| {
+                let rope = state.file_contents(&path).ok()?;
+                let parser = state
+                    .parsing_state
+                    .get(parser_info.id())
+                    .and_then(ParsingState::built)?;
+                Some(pretty::generic_tree(&parser, &rope))
+            });
+            let tree = if let Some(tree) = tree {
+                tree
+            } else {
+                return Ok(None);
+            };
+            let snapshot_path = testsuite::snapshot_path(&path);
+            let snapshot = snapshot_path.to_string_lossy().into_owned();
+            if let Some(doc) = state.documents.get(&snapshot_path) {
+                // The tests read an open snapshot from the editor, so it is edited there instead.
+                // Its change notification then reruns the tests.
+                let uri = lsp_ty::Url::from_file_path(&snapshot_path).map_err(|()| {
+                    error(jsonrpc::ErrorCode::InvalidParams, "Not a file".to_string())
+                })?;
+                let end = documents::byte_to_position(&doc.rope, doc.rope.len_bytes());
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(
+                    uri,
+                    vec![lsp_ty::TextEdit {
+                        range: lsp_ty::Range::new(lsp_ty::Position::new(0, 0), end),
+                        new_text: tree,
+                    }],
+                );
+                drop(state);
+                let response = self
+                    .client
+                    .apply_edit(lsp_ty::WorkspaceEdit {
+                        changes: Some(changes),
+                        ..Default::default()
+                    })
+                    .await?;
+                if !response.applied {
+                    let reason = response.failure_reason.unwrap_or_default();
+                    return Err(error(
+                        jsonrpc::ErrorCode::InternalError,
+                        format!("Unable to update snapshot: {reason}"),
+                    ));
+                }
+            } else {
+                std::fs::write(&snapshot_path, tree).map_err(|e| {
+                    error(
+                        jsonrpc::ErrorCode::InternalError,
+                        format!("Unable to write snapshot: {e}"),
+                    )
+                })?;
+                let diags = state.rerun_test_file(&path);
+                self.publish_diagnostics(&state, diags).await;
+            }
+            Ok(Some(snapshot))
         } else if params.cmd.starts_with("stategraph_") && params.cmd.ends_with(".cmd") {
             let path = std::path::PathBuf::from(&params.path);
             let parser_info = state.find_parser_info(&path);
@@ -74,8 +415,11 @@ impl Backend {
                     "all_edges" => StateGraphPretty::AllEdges,
                     _ => return Ok(None),
                 };
-                // FIXME
-                Ok(None)
+                Ok(state
+                    .parsing_state
+                    .get(parser_info.id())
+                    .and_then(ParsingState::built)
+                    .map(|parser| pretty::state_graph(&parser, &pretty_printer)))
             } else {
                 Ok(None)
             }
@@ -83,8 +427,13 @@ impl Backend {
             let path = std::path::PathBuf::from(&params.path);
             let parser_info = state.find_parser_info(&path);
             if let Some(parser_info) = parser_info {
-                // FIXME
-                Ok(None)
+                Ok(state.grammar(parser_info).map(|grm| {
+                    let syntax = state
+                        .file_contents(&parser_info.y_path)
+                        .map(|rope| syntax::YaccSyntax::new(&rope.to_string()))
+                        .unwrap_or_default();
+                    railroad::railroad_svg(&grm, &syntax)
+                }))
             } else {
                 Ok(None)
             }
@@ -98,6 +447,20 @@ impl Backend {
     }
 }
 
+/// Reads and deserialises the `nimbleparse.toml` of a workspace.
+async fn read_workspace(
+    workspace_path: &std::path::Path,
+) -> Result<nimbleparse_toml::Workspace, String> {
+    let toml_path = workspace_path.join("nimbleparse.toml");
+    let toml_file = tokio::fs::read_to_string(&toml_path)
+        .await
+        .map_err(|e| format!("{}: {e}", toml_path.display()))?;
+    toml::de::from_str(&toml_file).map_err(|e| format!("{}: {e}", toml_path.display()))
+}
+
+/// The id of the registration of every file watcher.
+const WATCHERS_ID: &str = "1";
+
 type Workspaces = std::collections::HashMap<std::path::PathBuf, WorkspaceCfg>;
 type ParserId = usize;
 
@@ -144,12 +507,64 @@ pub struct ParsingState {
     rt_parser_builders: Option<RTParserBuilder<'this, u32, lrlex::DefaultLexerTypes<u32>>>,
 }
 
+/// Borrows of everything in a `ParsingState` whose parser currently builds.
+pub struct BuiltParser<'a> {
+    lexerdef: &'a LexerDef,
+    grm: &'a yacc::YaccGrammar,
+    sgraph: &'a lrtable::StateGraph<u32>,
+    rtpb: &'a RTParserBuilder<'a, u32, lrlex::DefaultLexerTypes<u32>>,
+}
+
+impl ParsingState {
+    fn built(&self) -> Option<BuiltParser<'_>> {
+        match (&self.borrow_data().0, self.borrow_rt_parser_builders()) {
+            (Some((lexerdef, grm, sgraph, _)), Some(rtpb)) => Some(BuiltParser {
+                lexerdef,
+                grm,
+                sgraph,
+                rtpb,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The outcomes of a `[[tests]]` entry from when each of its files last ran, so that rerunning one
+/// file can summarize the entry without running the rest again.
+#[derive(Default)]
+struct TestOutcomes {
+    /// The outcomes of each file, `None` when it wasn't run because its parser doesn't build.
+    files: std::collections::BTreeMap<std::path::PathBuf, Option<Vec<testsuite::Outcome>>>,
+    /// The outcomes of generated inputs, along with the diagnostics of those which failed.
+    generated: Option<(Vec<testsuite::Outcome>, Vec<lsp_ty::Diagnostic>)>,
+}
+
 struct State {
     client_monitor: bool,
+    /// Whether the client's workspace edits can create files, which snapshot quick fixes need.
+    create_files: bool,
     extensions: std::collections::HashMap<std::ffi::OsString, ParserInfo>,
     toml: Workspaces,
-    warned_needs_restart: bool,
     parsing_state: Vec<ParsingState>,
+    documents: std::collections::HashMap<std::path::PathBuf, documents::Document>,
+    /// The inputs of the generators of each workspace, from when they were last run.
+    generated: std::collections::HashMap<
+        std::path::PathBuf,
+        Vec<(nimbleparse_toml::Generator, testsuite::Generated)>,
+    >,
+    /// The outcomes of each `[[tests]]` entry of each workspace, in the order of its entries.
+    test_outcomes: std::collections::HashMap<std::path::PathBuf, Vec<TestOutcomes>>,
+    /// The symbol index of each grammar, with the versions of the open grammar and lexer it was
+    /// built from, dropped whenever the parser is rebuilt.
+    symbol_indexes: std::sync::Mutex<
+        std::collections::HashMap<
+            std::path::PathBuf,
+            (
+                (Option<i32>, Option<i32>),
+                std::sync::Arc<symbols::SymbolIndex>,
+            ),
+        >,
+    >,
 }
 
 impl State {
@@ -191,6 +606,809 @@ impl State {
     fn parser_for(&self, path: &std::path::Path) -> Option<&ParserInfo> {
         path.extension().and_then(|ext| self.extensions.get(ext))
     }
+
+    /// Adds the parsers of a workspace to `extensions`, taking their ids from `free` before
+    /// allocating new ones. Returns the ids of the added parsers, none of which are built yet.
+    fn insert_parsers(
+        &mut self,
+        workspace_path: &std::path::Path,
+        free: &mut Vec<ParserId>,
+    ) -> Vec<ParserId> {
+        let mut ids = Vec::new();
+        let workspace = if let Some(cfg) = self.toml.get(workspace_path) {
+            &cfg.workspace
+        } else {
+            return ids;
+        };
+        for parser in workspace.parsers.get_ref().iter() {
+            // Ids are unique across workspaces, and index into `parsing_state`.
+            let id = free.pop().unwrap_or(self.parsing_state.len());
+            let parsing_state = ParsingState::new(ParserData(None), |_| None);
+            if id < self.parsing_state.len() {
+                self.parsing_state[id] = parsing_state;
+            } else {
+                self.parsing_state.push(parsing_state);
+            }
+            let l_path = workspace_path.join(parser.l_file.get_ref());
+            let y_path = workspace_path.join(parser.y_file.get_ref());
+            let extension = parser.extension.clone().into_inner();
+            // Want this to match the output of Path::extension() so trim any leading '.'.
+            let extension_str = extension
+                .strip_prefix('.')
+                .map(|x| x.to_string())
+                .unwrap_or(extension);
+            let extension = std::ffi::OsStr::new(&extension_str);
+            let parser_info = ParserInfo {
+                id,
+                l_path: workspace_path.join(l_path),
+                y_path: workspace_path.join(y_path),
+                recovery_kind: parser.recovery_kind,
+                yacc_kind: parser.yacc_kind,
+                extension: extension.to_owned(),
+                quiet: parser.quiet,
+            };
+
+            self.extensions
+                .insert(extension.to_os_string(), parser_info.clone());
+            ids.push(id);
+        }
+        ids
+    }
+
+    /// A registration watching each `nimbleparse.toml`, and the grammar, lexer and inputs of
+    /// every parser along with any snapshots, so changes made outside the editor are seen.
+    fn watchers(&self) -> lsp_ty::Registration {
+        let mut globs = vec!["**/nimbleparse.toml".to_string()];
+        for WorkspaceCfg { workspace, .. } in self.toml.values() {
+            for parser in workspace.parsers.get_ref() {
+                globs.push(format!("**/*{}", parser.extension.get_ref()));
+                for file in [parser.y_file.get_ref(), parser.l_file.get_ref()] {
+                    globs.push(format!("**/{}", file.display()));
+                }
+            }
+            if workspace.tests.iter().any(|test| test.snapshots) {
+                globs.push("**/*.tree".to_string());
+            }
+        }
+        globs.sort();
+        globs.dedup();
+        let watchers = globs
+            .into_iter()
+            .map(|glob| {
+                let mut reg = serde_json::Map::new();
+                reg.insert(
+                    "globPattern".to_string(),
+                    serde_json::value::Value::String(glob),
+                );
+                serde_json::value::Value::Object(reg)
+            })
+            .collect();
+        let mut options = serde_json::Map::new();
+        options.insert(
+            "watchers".to_string(),
+            serde_json::value::Value::Array(watchers),
+        );
+        lsp_ty::Registration {
+            id: WATCHERS_ID.to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: Some(serde_json::value::Value::Object(options)),
+        }
+    }
+
+    fn parser_info(&self, id: ParserId) -> Option<&ParserInfo> {
+        self.extensions
+            .values()
+            .find(|parser_info| parser_info.id() == id)
+    }
+
+    /// Builds the parser with the given id, returning the diagnostics for each of its files.
+    fn build_parser(&mut self, id: ParserId) -> diagnostics::Diagnostics {
+        let mut diags = diagnostics::Diagnostics::new();
+        if let Some(parser_info) = self.parser_info(id).cloned() {
+            // Files which aren't open have no version, so changes on disk only show up here.
+            self.symbol_indexes
+                .get_mut()
+                .unwrap()
+                .remove(&parser_info.y_path);
+            // The lexer and grammar are built independently, so that each file reports its own
+            // errors even while the other is broken.
+            let lexerdef = self.build_lexer(&parser_info, &mut diags);
+            let tables = self.build_grammar(&parser_info, &mut diags);
+            let data = match (lexerdef, tables) {
+                (Some(mut lexerdef), Some((grm, sgraph, stable))) => {
+                    // The sets borrow from `lexerdef`, so are copied out before it moves.
+                    let (mut missing_from_lexer, mut missing_from_parser) = {
+                        let rule_ids = grm
+                            .tokens_map()
+                            .iter()
+                            .map(|(&name, &tidx)| (name, u32::try_from(usize::from(tidx)).unwrap()))
+                            .collect::<std::collections::HashMap<_, _>>();
+                        let (missing_from_lexer, missing_from_parser) =
+                            lexerdef.set_rule_ids(&rule_ids);
+                        (
+                            missing_from_lexer
+                                .into_iter()
+                                .flatten()
+                                .map(str::to_string)
+                                .collect::<Vec<_>>(),
+                            missing_from_parser
+                                .into_iter()
+                                .flatten()
+                                .map(|(name, span)| (name.to_string(), span))
+                                .collect::<Vec<_>>(),
+                        )
+                    };
+                    missing_from_lexer.sort();
+                    missing_from_parser.sort_by_key(|(_, span)| span.start());
+                    self.token_lints(
+                        &parser_info,
+                        &missing_from_lexer,
+                        &missing_from_parser,
+                        &mut diags,
+                    );
+                    ParserData(Some((lexerdef, grm, sgraph, stable)))
+                }
+                _ => ParserData(None),
+            };
+            let recovery_kind = parser_info.recovery_kind;
+            if let Some(parsing_state) = self.parsing_state.get_mut(id) {
+                *parsing_state = ParsingState::new(data, |data| {
+                    data.0.as_ref().map(|(_, grm, _, stable)| {
+                        RTParserBuilder::new(grm, stable).recoverer(recovery_kind)
+                    })
+                });
+            }
+        }
+        diags
+    }
+
+    /// Adds the lints about tokens missing from either the lexer or grammar to `diags`.
+    fn token_lints(
+        &self,
+        parser_info: &ParserInfo,
+        missing_from_lexer: &[String],
+        missing_from_parser: &[(String, cfgrammar::Span)],
+        diags: &mut diagnostics::Diagnostics,
+    ) {
+        let file = |path: &std::path::Path| {
+            Some((
+                lsp_ty::Url::from_file_path(path).ok()?,
+                self.file_contents(path).ok()?,
+            ))
+        };
+        if let (Some((y_uri, y_rope)), Some((l_uri, l_rope))) =
+            (file(&parser_info.y_path), file(&parser_info.l_path))
+        {
+            let syntax = syntax::YaccSyntax::new(&y_rope.to_string());
+            let (y_lints, l_lints) = lints::token_lints(
+                &syntax,
+                &y_rope,
+                &l_rope,
+                missing_from_lexer,
+                missing_from_parser,
+            );
+            diags.entry(y_uri).or_default().extend(y_lints);
+            diags.entry(l_uri).or_default().extend(l_lints);
+        }
+    }
+
+    /// Lexes and parses an input file with the current build of its parser.
+    fn parse_file(
+        &self,
+        id: ParserId,
+        path: &std::path::Path,
+    ) -> Option<(lsp_ty::Url, Vec<lsp_ty::Diagnostic>)> {
+        let uri = lsp_ty::Url::from_file_path(path).ok()?;
+        let rope = self.file_contents(path).ok()?;
+        let parsing_state = self.parsing_state.get(id)?;
+        let diags = match parsing_state.built() {
+            Some(parser) => {
+                let diags =
+                    diagnostics::parse_diagnostics(parser.lexerdef, parser.grm, parser.rtpb, &rope);
+                match self
+                    .test_of(path)
+                    .map(|test| (test.kind.get_ref(), test.pass))
+                {
+                    Some((nimbleparse_toml::TestKind::Dir(_), pass)) => {
+                        testsuite::test_diagnostics(pass, diags, lsp_ty::Range::default()).1
+                    }
+                    // The diagnostics of an inputs file are those of each input, from `run_tests`.
+                    Some(_) => return None,
+                    None => diags,
+                }
+            }
+            // The parser doesn't currently build, the diagnostics of its grammar and lexer say why.
+            None => Vec::new(),
+        };
+        Some((uri, diags))
+    }
+
+    /// The path of the workspace whose `nimbleparse.toml` configures the given parser.
+    fn workspace_of(&self, parser_info: &ParserInfo) -> Option<std::path::PathBuf> {
+        self.toml
+            .iter()
+            .find(|(workspace_path, cfg)| {
+                cfg.workspace.parsers.get_ref().iter().any(|parser| {
+                    workspace_path.join(parser.y_file.get_ref()) == parser_info.y_path
+                })
+            })
+            .map(|(workspace_path, _)| workspace_path.clone())
+    }
+
+    /// The workspace whose `nimbleparse.toml` is `path`.
+    fn config_of(&self, path: &std::path::Path) -> Option<std::path::PathBuf> {
+        let workspace_path = path.parent()?;
+        let is_config = path.file_name() == Some(std::ffi::OsStr::new("nimbleparse.toml"));
+        (is_config && self.toml.contains_key(workspace_path)).then(|| workspace_path.to_path_buf())
+    }
+
+    /// The workspaces of the parsers whose grammar or lexer is `path`.
+    fn grammar_workspaces(&self, path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let mut workspaces = self
+            .extensions
+            .values()
+            .filter(|parser_info| parser_info.is_parser(path) || parser_info.is_lexer(path))
+            .filter_map(|parser_info| self.workspace_of(parser_info))
+            .collect::<Vec<_>>();
+        workspaces.sort();
+        workspaces.dedup();
+        workspaces
+    }
+
+    /// The `[[tests]]` entry of the test directory or inputs file containing `path`.
+    fn test_of(&self, path: &std::path::Path) -> Option<&nimbleparse_toml::TestDir> {
+        self.test_workspace_of(path).map(|(_, test)| test)
+    }
+
+    /// Like `test_of`, along with the path of the workspace of the test.
+    fn test_workspace_of(
+        &self,
+        path: &std::path::Path,
+    ) -> Option<(&std::path::PathBuf, &nimbleparse_toml::TestDir)> {
+        self.toml.iter().find_map(|(workspace_path, cfg)| {
+            cfg.workspace
+                .tests
+                .iter()
+                .find(|test| match test.kind.get_ref() {
+                    nimbleparse_toml::TestKind::Dir(dir) => {
+                        path.starts_with(workspace_path.join(dir))
+                    }
+                    nimbleparse_toml::TestKind::Inputs { file, .. } => {
+                        path == workspace_path.join(file)
+                    }
+                    nimbleparse_toml::TestKind::Generated { .. } => false,
+                })
+                .map(|test| (workspace_path, test))
+        })
+    }
+
+    /// The generators of the `[[tests]]` of a workspace.
+    fn generators(&self, workspace_path: &std::path::Path) -> Vec<nimbleparse_toml::Generator> {
+        self.toml.get(workspace_path).map_or_else(Vec::new, |cfg| {
+            cfg.workspace
+                .tests
+                .iter()
+                .filter_map(|test| match test.kind.get_ref() {
+                    nimbleparse_toml::TestKind::Generated { generator, .. } => Some(generator),
+                    _ => None,
+                })
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Runs the tests of a workspace, returning the diagnostics of each test file along with a
+    /// summary of each `[[tests]]` entry for its `nimbleparse.toml`. Failures of generated inputs
+    /// are also reported on their `[[tests]]` entry, as they have no file of their own.
+    fn run_tests(&mut self, workspace_path: &std::path::Path) -> diagnostics::Diagnostics {
+        let mut diags = diagnostics::Diagnostics::new();
+        let outcomes = if let Some(cfg) = self.toml.get(workspace_path) {
+            cfg.workspace
+                .tests
+                .iter()
+                .map(|test| self.run_test(workspace_path, test, &mut diags))
+                .collect()
+        } else {
+            return diags;
+        };
+        self.test_outcomes
+            .insert(workspace_path.to_path_buf(), outcomes);
+        diags.extend(self.test_summaries(workspace_path));
+        diags
+    }
+
+    /// Reruns just the test file `path`, or the file whose snapshot it is. The summary of its
+    /// `[[tests]]` entry uses the outcomes of the entry's other files from when they last ran.
+    fn rerun_test_file(&mut self, path: &std::path::Path) -> diagnostics::Diagnostics {
+        let found = self
+            .test_workspace_of(path)
+            .and_then(|(workspace_path, test)| {
+                let tests = &self.toml.get(workspace_path)?.workspace.tests;
+                let idx = tests.iter().position(|other| std::ptr::eq(other, test))?;
+                Some((workspace_path.clone(), idx))
+            });
+        let (workspace_path, idx) = if let Some(found) = found {
+            found
+        } else {
+            return diagnostics::Diagnostics::new();
+        };
+        if !self.test_outcomes.contains_key(&workspace_path) {
+            return self.run_tests(&workspace_path);
+        }
+        let mut diags = diagnostics::Diagnostics::new();
+        let test = &self.toml[&workspace_path].workspace.tests[idx];
+        let path = if test.snapshots && testsuite::is_snapshot(path) {
+            path.with_extension("")
+        } else {
+            path.to_path_buf()
+        };
+        let exists = self.documents.contains_key(&path) || path.is_file();
+        let is_dir = matches!(test.kind.get_ref(), nimbleparse_toml::TestKind::Dir(_));
+        let outcomes = if !exists {
+            // A deleted file no longer has diagnostics.
+            if let Ok(uri) = lsp_ty::Url::from_file_path(&path) {
+                diags.insert(uri, Vec::new());
+            }
+            None
+        } else if is_dir && self.parser_for(&path).is_none() {
+            None
+        } else {
+            Some(self.run_test_file(test, &path, &mut diags))
+        };
+        if let Some(results) = self
+            .test_outcomes
+            .get_mut(&workspace_path)
+            .and_then(|results| results.get_mut(idx))
+        {
+            match outcomes {
+                Some(outcomes) => results.files.insert(path, outcomes),
+                None => results.files.remove(&path),
+            };
+        }
+        diags.extend(self.test_summaries(&workspace_path));
+        diags
+    }
+
+    /// Runs every file of a `[[tests]]` entry, or its generated inputs.
+    fn run_test(
+        &self,
+        workspace_path: &std::path::Path,
+        test: &nimbleparse_toml::TestDir,
+        diags: &mut diagnostics::Diagnostics,
+    ) -> TestOutcomes {
+        let mut outcomes = TestOutcomes::default();
+        match test.kind.get_ref() {
+            nimbleparse_toml::TestKind::Dir(dir) => {
+                for path in testsuite::files(&workspace_path.join(dir)) {
+                    if test.snapshots && testsuite::is_snapshot(&path) {
+                        continue;
+                    }
+                    if self.parser_for(&path).is_some() {
+                        let file_outcomes = self.run_test_file(test, &path, diags);
+                        outcomes.files.insert(path, file_outcomes);
+                    }
+                }
+            }
+            nimbleparse_toml::TestKind::Inputs { file, .. } => {
+                let path = workspace_path.join(file);
+                let file_outcomes = self.run_test_file(test, &path, diags);
+                outcomes.files.insert(path, file_outcomes);
+            }
+            nimbleparse_toml::TestKind::Generated {
+                extension,
+                generator,
+            } => {
+                let extension = std::ffi::OsStr::new(extension.trim_start_matches('.'));
+                let parser = self
+                    .extensions
+                    .get(extension)
+                    .and_then(|parser_info| self.parsing_state.get(parser_info.id()))
+                    .and_then(ParsingState::built);
+                // Generators are run by `Backend::run_generators`, until then a test isn't run.
+                let generated = self.generated.get(workspace_path).and_then(|generated| {
+                    generated
+                        .iter()
+                        .find(|(cached, _)| cached == generator)
+                        .map(|(_, generated)| generated)
+                });
+                let toml_path = workspace_path.join("nimbleparse.toml");
+                let location = lsp_ty::Url::from_file_path(&toml_path).ok().map(|uri| {
+                    let range = self.file_contents(&toml_path).map_or_else(
+                        |_| lsp_ty::Range::default(),
+                        |rope| testsuite::toml_range(&rope, test.kind.span()),
+                    );
+                    lsp_ty::Location::new(uri, range)
+                });
+                if let (Some(parser), Some(location), Some(generated)) =
+                    (parser, location, generated)
+                {
+                    let mut generated_outcomes = Vec::new();
+                    let failures = testsuite::run_generated(
+                        &parser,
+                        generated,
+                        test.pass,
+                        &location,
+                        &mut generated_outcomes,
+                    );
+                    outcomes.generated = Some((generated_outcomes, failures));
+                }
+            }
+        }
+        outcomes
+    }
+
+    /// Runs one file of a test directory or an inputs file, `None` when its parser doesn't build.
+    fn run_test_file(
+        &self,
+        test: &nimbleparse_toml::TestDir,
+        path: &std::path::Path,
+        diags: &mut diagnostics::Diagnostics,
+    ) -> Option<Vec<testsuite::Outcome>> {
+        let mut outcomes = Vec::new();
+        let ran = match test.kind.get_ref() {
+            nimbleparse_toml::TestKind::Dir(_) => {
+                self.parser_for(path).map_or(false, |parser_info| {
+                    self.run_file_test(
+                        parser_info,
+                        path,
+                        test.pass,
+                        test.snapshots,
+                        &mut outcomes,
+                        diags,
+                    )
+                })
+            }
+            nimbleparse_toml::TestKind::Inputs {
+                extension, format, ..
+            } => {
+                let extension = std::ffi::OsStr::new(extension.trim_start_matches('.'));
+                self.extensions.get(extension).map_or(false, |parser_info| {
+                    self.run_inputs_test(parser_info, path, format, test.pass, &mut outcomes, diags)
+                })
+            }
+            nimbleparse_toml::TestKind::Generated { .. } => false,
+        };
+        ran.then_some(outcomes)
+    }
+
+    /// The summary of each `[[tests]]` entry of a workspace for its `nimbleparse.toml`, from the
+    /// outcomes of when its tests last ran.
+    fn test_summaries(
+        &self,
+        workspace_path: &std::path::Path,
+    ) -> Option<(lsp_ty::Url, Vec<lsp_ty::Diagnostic>)> {
+        let cfg = self.toml.get(workspace_path)?;
+        let results = self.test_outcomes.get(workspace_path)?;
+        let toml_path = workspace_path.join("nimbleparse.toml");
+        let toml_uri = lsp_ty::Url::from_file_path(&toml_path).ok()?;
+        let mut summaries = Vec::new();
+        if let Ok(toml_rope) = self.file_contents(&toml_path) {
+            for (test, results) in cfg.workspace.tests.iter().zip(results) {
+                let range = testsuite::toml_range(&toml_rope, test.kind.span());
+                let mut outcomes = results
+                    .files
+                    .values()
+                    .flatten()
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let mut not_run = results
+                    .files
+                    .values()
+                    .filter(|outcomes| outcomes.is_none())
+                    .count();
+                match &results.generated {
+                    Some((generated, failures)) => {
+                        outcomes.extend(generated.iter().cloned());
+                        summaries.extend(failures.iter().cloned());
+                    }
+                    None => {
+                        let generated = matches!(
+                            test.kind.get_ref(),
+                            nimbleparse_toml::TestKind::Generated { .. }
+                        );
+                        not_run += usize::from(generated);
+                    }
+                }
+                summaries.push(testsuite::summary(range, &outcomes, not_run));
+            }
+        }
+        Some((toml_uri, summaries))
+    }
+
+    /// The files of the test directories and inputs files of a workspace.
+    fn test_files(&self, workspace_path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let mut paths = Vec::new();
+        let tests = self
+            .toml
+            .get(workspace_path)
+            .map_or(&[][..], |cfg| cfg.workspace.tests.as_slice());
+        for test in tests {
+            match test.kind.get_ref() {
+                nimbleparse_toml::TestKind::Dir(dir) => {
+                    paths.extend(testsuite::files(&workspace_path.join(dir)));
+                }
+                nimbleparse_toml::TestKind::Inputs { file, .. } => {
+                    paths.push(workspace_path.join(file));
+                }
+                nimbleparse_toml::TestKind::Generated { .. } => (),
+            }
+        }
+        paths
+    }
+
+    /// Parses a file of a test directory, returning false if its parser doesn't build.
+    ///
+    /// With `snapshots`, the test also fails when its parse tree differs from its snapshot.
+    fn run_file_test(
+        &self,
+        parser_info: &ParserInfo,
+        path: &std::path::Path,
+        pass: bool,
+        snapshots: bool,
+        outcomes: &mut Vec<testsuite::Outcome>,
+        diags: &mut diagnostics::Diagnostics,
+    ) -> bool {
+        let parser = self
+            .parsing_state
+            .get(parser_info.id())
+            .and_then(ParsingState::built);
+        let (parser, rope, uri) = match (
+            parser,
+            self.file_contents(path),
+            lsp_ty::Url::from_file_path(path),
+        ) {
+            (Some(parser), Ok(rope), Ok(uri)) => (parser, rope, uri),
+            _ => return false,
+        };
+        let parse_diags =
+            diagnostics::parse_diagnostics(parser.lexerdef, parser.grm, parser.rtpb, &rope);
+        let (mut passed, mut test_diags) =
+            testsuite::test_diagnostics(pass, parse_diags, lsp_ty::Range::default());
+        if snapshots {
+            let snapshot = self.file_contents(&testsuite::snapshot_path(path)).ok();
+            let snapshot = snapshot.map(|rope| rope.to_string());
+            let tree = pretty::generic_tree(&parser, &rope);
+            if let Some(diag) = testsuite::snapshot_diagnostic(path, &tree, snapshot.as_deref()) {
+                test_diags.push(diag);
+                passed = false;
+            }
+        }
+        diags.insert(uri.clone(), test_diags);
+        outcomes.push(testsuite::Outcome {
+            location: lsp_ty::Location::new(uri, lsp_ty::Range::default()),
+            passed,
+        });
+        true
+    }
+
+    /// Parses each input of an inputs file, returning false if its parser doesn't build.
+    ///
+    /// A file which can't be read or split is reported as a single failed test.
+    fn run_inputs_test(
+        &self,
+        parser_info: &ParserInfo,
+        path: &std::path::Path,
+        format: &nimbleparse_toml::InputsFormat,
+        pass: bool,
+        outcomes: &mut Vec<testsuite::Outcome>,
+        diags: &mut diagnostics::Diagnostics,
+    ) -> bool {
+        let parser = self
+            .parsing_state
+            .get(parser_info.id())
+            .and_then(ParsingState::built);
+        let (parser, uri) = match (parser, lsp_ty::Url::from_file_path(path)) {
+            (Some(parser), Ok(uri)) => (parser, uri),
+            _ => return false,
+        };
+        let inputs = self
+            .file_contents(path)
+            .map_err(|e| e.to_string())
+            .and_then(|rope| Ok((testsuite::split_inputs(&rope.to_string(), format)?, rope)));
+        let (inputs, rope) = match inputs {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                let diag = lsp_ty::Diagnostic {
+                    range: lsp_ty::Range::default(),
+                    severity: Some(lsp_ty::DiagnosticSeverity::ERROR),
+                    source: Some(diagnostics::SOURCE.to_string()),
+                    message: format!("Unable to read the test inputs: {e}"),
+                    ..Default::default()
+                };
+                diags.insert(uri.clone(), vec![diag]);
+                outcomes.push(testsuite::Outcome {
+                    location: lsp_ty::Location::new(uri, lsp_ty::Range::default()),
+                    passed: false,
+                });
+                return true;
+            }
+        };
+        let mut file_diags = Vec::new();
+        for (i, input) in inputs.iter().enumerate() {
+            let range = documents::span_to_range(&rope, input.span);
+            let parse_diags =
+                diagnostics::parse_errors(parser.lexerdef, parser.grm, parser.rtpb, &input.text)
+                    .into_iter()
+                    .map(|(span, message)| lsp_ty::Diagnostic {
+                        range: documents::span_to_range(&rope, input.file_span(span)),
+                        severity: Some(lsp_ty::DiagnosticSeverity::ERROR),
+                        source: Some(diagnostics::SOURCE.to_string()),
+                        message: if input.verbatim {
+                            message
+                        } else {
+                            format!("In input {}: {message}", i + 1)
+                        },
+                        ..Default::default()
+                    })
+                    .collect();
+            let (passed, input_diags) =
+                testsuite::test_diagnostics(input.pass.unwrap_or(pass), parse_diags, range);
+            file_diags.extend(input_diags);
+            outcomes.push(testsuite::Outcome {
+                location: lsp_ty::Location::new(uri.clone(), range),
+                passed,
+            });
+        }
+        diags.insert(uri, file_diags);
+        true
+    }
+
+    /// Builds just the grammar of a parser, for features which don't need it to have a lexer or state table.
+    fn grammar(&self, parser_info: &ParserInfo) -> Option<yacc::YaccGrammar> {
+        let rope = self.file_contents(&parser_info.y_path).ok()?;
+        yacc::YaccGrammar::new(parser_info.yacc_kind, &rope.to_string()).ok()
+    }
+
+    /// The parser whose grammar or lexer is `path`.
+    fn parser_of_file(&self, path: &std::path::Path) -> Option<&ParserInfo> {
+        self.extensions
+            .values()
+            .find(|parser_info| parser_info.is_parser(path) || parser_info.is_lexer(path))
+    }
+
+    /// The symbol index of a parser, reused until either of its files changes.
+    fn symbol_index(
+        &self,
+        parser_info: &ParserInfo,
+    ) -> Option<std::sync::Arc<symbols::SymbolIndex>> {
+        let version = |path: &std::path::Path| self.documents.get(path).map(|doc| doc.version);
+        let versions = (version(&parser_info.y_path), version(&parser_info.l_path));
+        let mut indexes = self.symbol_indexes.lock().unwrap();
+        if let Some((built, index)) = indexes.get(&parser_info.y_path) {
+            if *built == versions {
+                return Some(index.clone());
+            }
+        }
+        let source = |path: &std::path::Path| {
+            Some(symbols::Source {
+                uri: lsp_ty::Url::from_file_path(path).ok()?,
+                rope: self.file_contents(path).ok()?,
+            })
+        };
+        let y = source(&parser_info.y_path)?;
+        let l = source(&parser_info.l_path);
+        let grm = yacc::YaccGrammar::new(parser_info.yacc_kind, &y.rope.to_string()).ok();
+        let lexerdef = l
+            .as_ref()
+            .and_then(|l| LexerDef::from_str(&l.rope.to_string()).ok());
+        let index = std::sync::Arc::new(symbols::SymbolIndex::new(y, l, grm, lexerdef));
+        indexes.insert(parser_info.y_path.clone(), (versions, index.clone()));
+        Some(index)
+    }
+
+    /// The contents of `uri` when it is the grammar of a parser.
+    fn grammar_contents(&self, uri: &lsp_ty::Url) -> Option<ropey::Rope> {
+        let path = uri.to_file_path().ok()?;
+        self.parser_of_file(&path)
+            .filter(|parser_info| parser_info.is_parser(&path))?;
+        self.file_contents(&path).ok()
+    }
+
+    /// The symbol index of the parser whose grammar or lexer is `uri`.
+    fn symbol_index_for(&self, uri: &lsp_ty::Url) -> Option<std::sync::Arc<symbols::SymbolIndex>> {
+        let path = uri.to_file_path().ok()?;
+        self.symbol_index(self.parser_of_file(&path)?)
+    }
+
+    /// The open files which are inputs to the given parser.
+    fn open_inputs(&self, parser_info: &ParserInfo) -> Vec<std::path::PathBuf> {
+        self.documents
+            .keys()
+            .filter(|path| path.extension() == Some(parser_info.extension.as_os_str()))
+            .cloned()
+            .collect()
+    }
+
+    fn build_lexer(
+        &self,
+        parser_info: &ParserInfo,
+        diags: &mut diagnostics::Diagnostics,
+    ) -> Option<LexerDef> {
+        let uri = lsp_ty::Url::from_file_path(&parser_info.l_path).ok()?;
+        let rope = self.file_contents(&parser_info.l_path).ok()?;
+        match LexerDef::from_str(&rope.to_string()) {
+            Ok(lexerdef) => {
+                diags.insert(uri, Vec::new());
+                Some(lexerdef)
+            }
+            Err(errs) => {
+                let l_diags = errs
+                    .iter()
+                    .map(|err| {
+                        diagnostics::spanned_diagnostic(
+                            err,
+                            &uri,
+                            &rope,
+                            lsp_ty::DiagnosticSeverity::ERROR,
+                        )
+                    })
+                    .collect();
+                diags.insert(uri, l_diags);
+                None
+            }
+        }
+    }
+
+    /// Builds the grammar and its state table, reporting errors and conflicts.
+    fn build_grammar(
+        &self,
+        parser_info: &ParserInfo,
+        diags: &mut diagnostics::Diagnostics,
+    ) -> Option<(
+        yacc::YaccGrammar,
+        lrtable::StateGraph<u32>,
+        lrtable::StateTable<u32>,
+    )> {
+        let uri = lsp_ty::Url::from_file_path(&parser_info.y_path).ok()?;
+        let rope = self.file_contents(&parser_info.y_path).ok()?;
+        let src = rope.to_string();
+        let grm = match yacc::YaccGrammar::new(parser_info.yacc_kind, &src) {
+            Ok(grm) => grm,
+            Err(errs) => {
+                let y_diags = errs
+                    .iter()
+                    .map(|err| {
+                        diagnostics::spanned_diagnostic(
+                            err,
+                            &uri,
+                            &rope,
+                            lsp_ty::DiagnosticSeverity::ERROR,
+                        )
+                    })
+                    .collect();
+                diags.insert(uri, y_diags);
+                return None;
+            }
+        };
+        let syntax = syntax::YaccSyntax::new(&src);
+        let mut y_diags = lints::rule_lints(&grm, &syntax, &rope);
+        let tables = match lrtable::from_yacc(&grm, lrtable::Minimiser::Pager) {
+            Ok((sgraph, stable)) => {
+                y_diags.extend(diagnostics::conflict_diagnostics(
+                    &grm, &sgraph, &stable, &syntax, &uri, &rope,
+                ));
+                Some((sgraph, stable))
+            }
+            Err(err) => {
+                y_diags.push(diagnostics::state_table_diagnostic(
+                    &err, &grm, &syntax, &uri, &rope,
+                ));
+                None
+            }
+        };
+        diags.insert(uri, y_diags);
+        tables.map(|(sgraph, stable)| (grm, sgraph, stable))
+    }
+
+    /// Returns the contents of `path`, preferring the editor's buffer over the disk when the file is open.
+    fn file_contents(&self, path: &std::path::Path) -> std::io::Result<ropey::Rope> {
+        if let Some(doc) = self.documents.get(path) {
+            Ok(doc.rope.clone())
+        } else {
+            ropey::Rope::from_reader(std::io::BufReader::new(std::fs::File::open(path)?))
+        }
+    }
 }
 
 fn initialize_failed(reason: String) -> jsonrpc::Result<lsp_ty::InitializeResult> {
@@ -225,6 +1443,17 @@ impl LanguageServer for Backend {
 
         let mut state = self.state.lock().await;
 
+        state.create_files = caps
+            .workspace
+            .as_ref()
+            .and_then(|wrk| wrk.workspace_edit.as_ref())
+            .map_or(false, |edit| {
+                edit.document_changes.unwrap_or(false)
+                    && edit.resource_operations.as_ref().map_or(false, |ops| {
+                        ops.contains(&lsp_ty::ResourceOperationKind::Create)
+                    })
+            });
+
         // vscode only supports dynamic_registration
         // neovim supports neither dynamic or static registration of this yet.
         state.client_monitor = caps.workspace.map_or(false, |wrk| {
@@ -233,25 +1462,62 @@ impl LanguageServer for Backend {
             })
         });
 
-        let paths = params.workspace_folders.unwrap();
-        let paths = paths
-            .iter()
-            .map(|folder| folder.uri.to_file_path().unwrap());
-        state.toml.extend(paths.map(|workspace_path| {
-            let toml_path = workspace_path.join("nimbleparse.toml");
-            // We should probably fix this, to not be sync when we implement reloading the toml file on change...
-            let toml_file = std::fs::read_to_string(toml_path).unwrap();
-            let workspace: nimbleparse_toml::Workspace =
-                toml::de::from_str(toml_file.as_str()).unwrap();
-            (workspace_path, WorkspaceCfg { workspace })
-        }));
+        for folder in params.workspace_folders.unwrap() {
+            let workspace_path = folder.uri.to_file_path().unwrap();
+            match read_workspace(&workspace_path).await {
+                Ok(workspace) => {
+                    state
+                        .toml
+                        .insert(workspace_path, WorkspaceCfg { workspace });
+                }
+                Err(e) => return initialize_failed(e),
+            }
+        }
 
         Ok(lsp_ty::InitializeResult {
             capabilities: lsp_ty::ServerCapabilities {
-                text_document_sync: Some(lsp_ty::TextDocumentSyncCapability::Kind(
-                    lsp_ty::TextDocumentSyncKind::INCREMENTAL,
+                text_document_sync: Some(lsp_ty::TextDocumentSyncCapability::Options(
+                    lsp_ty::TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(lsp_ty::TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(lsp_ty::TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 hover_provider: Some(lsp_ty::HoverProviderCapability::Simple(true)),
+                definition_provider: Some(lsp_ty::OneOf::Left(true)),
+                document_symbol_provider: Some(lsp_ty::OneOf::Left(true)),
+                document_formatting_provider: Some(lsp_ty::OneOf::Left(true)),
+                document_range_formatting_provider: Some(lsp_ty::OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(
+                    lsp_ty::DocumentOnTypeFormattingOptions {
+                        first_trigger_character: "\n".to_string(),
+                        more_trigger_character: None,
+                    },
+                ),
+                call_hierarchy_provider: Some(lsp_ty::CallHierarchyServerCapability::Simple(true)),
+                workspace_symbol_provider: Some(lsp_ty::OneOf::Left(true)),
+                references_provider: Some(lsp_ty::OneOf::Left(true)),
+                semantic_tokens_provider: Some(
+                    lsp_ty::SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        lsp_ty::SemanticTokensOptions {
+                            work_done_progress_options: Default::default(),
+                            legend: semantic_tokens::legend(),
+                            range: Some(true),
+                            full: Some(lsp_ty::SemanticTokensFullOptions::Bool(true)),
+                        },
+                    ),
+                ),
+                rename_provider: Some(lsp_ty::OneOf::Right(lsp_ty::RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                code_action_provider: Some(lsp_ty::CodeActionProviderCapability::Options(
+                    lsp_ty::CodeActionOptions {
+                        code_action_kinds: Some(vec![lsp_ty::CodeActionKind::QUICKFIX]),
+                        ..Default::default()
+                    },
+                )),
                 completion_provider: Some(lsp_ty::CompletionOptions::default()),
                 // Can't return this *and* register in the editor client because of vscode.
                 // returning this doesn't seem to handle arguments, or work with commands
@@ -265,114 +1531,492 @@ impl LanguageServer for Backend {
     }
 
     async fn initialized(&mut self, params: lsp_ty::InitializedParams) {
-        let mut state = self.state.lock().await;
-        let state = state.deref_mut();
-        let mut globs: Vec<lsp_ty::Registration> = Vec::new();
+        let mut state_guard = self.state.lock().await;
+        let state = state_guard.deref_mut();
+        /* The lsp_types and lsp specification documentation say to register this dynamically
+         * rather than statically, I'm not sure of a good place we can register it besides here.
+         * Unfortunately register_capability returns a result, and this notification cannot return one;
+         * given that this has to manually match errors and can't use much in the way of ergonomics.
+         */
         if state.client_monitor {
-            for WorkspaceCfg { workspace, .. } in state.toml.values() {
-                for parser in workspace.parsers.get_ref() {
-                    let glob = format!("**/*{}", parser.extension.get_ref());
-                    let mut reg = serde_json::Map::new();
-                    reg.insert(
-                        "globPattern".to_string(),
-                        serde_json::value::Value::String(glob),
-                    );
-                    let mut watchers = serde_json::Map::new();
-                    let blah = vec![serde_json::value::Value::Object(reg)];
-                    watchers.insert(
-                        "watchers".to_string(),
-                        serde_json::value::Value::Array(blah),
-                    );
-
-                    globs.push(lsp_ty::Registration {
-                        id: "1".to_string(),
-                        method: "workspace/didChangeWatchedFiles".to_string(),
-                        register_options: Some(serde_json::value::Value::Object(watchers)),
-                    });
-                }
+            if let Err(e) = self.register_watchers(state).await {
+                panic!("{}", e);
             }
+        }
+        // construct extension lookup table
+        let workspace_paths = state.toml.keys().cloned().collect::<Vec<_>>();
+        for workspace_path in &workspace_paths {
+            state.insert_parsers(workspace_path, &mut Vec::new());
+        }
+
+        let ids = state
+            .extensions
+            .values()
+            .map(ParserInfo::id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let diags = state.build_parser(id);
+            self.publish_diagnostics(state, diags).await;
+        }
+        for workspace_path in &workspace_paths {
+            let diags = state.run_tests(workspace_path);
+            self.publish_diagnostics(state, diags).await;
+        }
+        drop(state_guard);
+        for workspace_path in &workspace_paths {
+            self.run_generators(workspace_path, false).await;
+        }
+
+        self.client
+            .log_message(lsp_ty::MessageType::LOG, "initialized!")
+            .await;
+    }
+
+    async fn shutdown(&mut self) -> jsonrpc::Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&mut self, params: lsp_ty::DidOpenTextDocumentParams) {
+        let doc = params.text_document;
+        if let Ok(path) = doc.uri.to_file_path() {
+            let mut state = self.state.lock().await;
+            state.documents.insert(
+                path.clone(),
+                documents::Document::new(&doc.text, doc.version),
+            );
+            drop(state);
+            self.file_changed(&path).await;
+        } else {
             self.client
                 .log_message(
-                    lsp_ty::MessageType::LOG,
-                    format!("registering! {:?}", globs.clone()),
+                    lsp_ty::MessageType::WARNING,
+                    format!("ignoring non-file document: {}", doc.uri),
                 )
                 .await;
         }
+    }
 
-        /* The lsp_types and lsp specification documentation say to register this dynamically
-         * rather than statically, I'm not sure of a good place we can register it besides here.
-         * Unfortunately register_capability returns a result, and this notification cannot return one;
-         * given that this has to manually match errors and can't use much in the way of ergonomics.
-         */
-        if state.client_monitor {
-            if let Err(e) = self.client.register_capability(globs).await {
+    async fn did_change(&mut self, params: lsp_ty::DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if let Ok(path) = uri.to_file_path() {
+            let mut state = self.state.lock().await;
+            if let Some(doc) = state.documents.get_mut(&path) {
+                // Changes must be applied in order, each one is relative to the result of the last.
+                for change in params.content_changes {
+                    doc.apply_change(change);
+                }
+                doc.version = params.text_document.version;
+                drop(state);
+                self.file_changed(&path).await;
+            } else {
+                drop(state);
                 self.client
                     .log_message(
                         lsp_ty::MessageType::ERROR,
-                        format!(
-                            "registering for {}: {}",
-                            "workspace/didChangeWatchedFiles", e
-                        ),
+                        format!("change to a document which isn't open: {uri}"),
                     )
                     .await;
-                panic!("{}", e);
             }
         }
-        // construct extension lookup table
+    }
+
+    async fn code_action(
+        &mut self,
+        params: lsp_ty::CodeActionParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let path = if let Ok(path) = uri.to_file_path() {
+            path
+        } else {
+            return Ok(None);
+        };
+        let state = self.state.lock().await;
+        let parser_info = if let Some(parser_info) = state.parser_for(&path) {
+            parser_info
+        } else {
+            return Ok(None);
+        };
+        let rope = if let Ok(rope) = state.file_contents(&path) {
+            rope
+        } else {
+            return Ok(None);
+        };
+        let parser = if let Some(parser) = state
+            .parsing_state
+            .get(parser_info.id())
+            .and_then(ParsingState::built)
         {
-            let extensions = &mut state.extensions;
-            for (workspace_path, workspace_cfg) in (state.toml).iter() {
-                let workspace = &workspace_cfg.workspace;
-                for (id, parser) in workspace.parsers.get_ref().iter().enumerate() {
-                    let l_path = workspace_path.join(parser.l_file.get_ref());
-                    let y_path = workspace_path.join(parser.y_file.get_ref());
-                    let extension = parser.extension.clone().into_inner();
-                    // Want this to match the output of Path::extension() so trim any leading '.'.
-                    let extension_str = extension
-                        .strip_prefix('.')
-                        .map(|x| x.to_string())
-                        .unwrap_or(extension);
-                    let extension = std::ffi::OsStr::new(&extension_str);
-                    let parser_info = ParserInfo {
-                        id,
-                        l_path: workspace_path.join(l_path),
-                        y_path: workspace_path.join(y_path),
-                        recovery_kind: parser.recovery_kind,
-                        yacc_kind: parser.yacc_kind,
-                        extension: extension.to_owned(),
-                        quiet: parser.quiet,
-                    };
+            parser
+        } else {
+            return Ok(None);
+        };
+        let mut actions = Vec::new();
+        if state.test_of(&path).map_or(false, |test| test.snapshots) {
+            let snapshot = state.file_contents(&testsuite::snapshot_path(&path)).ok();
+            actions.extend(testsuite::snapshot_actions(
+                &parser,
+                &path,
+                &rope,
+                snapshot.as_ref(),
+                state.create_files,
+                &params.context.diagnostics,
+            ));
+        }
+        // Only CPCT+ produces repair sequences.
+        if matches!(parser_info.recovery_kind, lrpar::RecoveryKind::CPCTPlus) {
+            actions.extend(repairs::repair_actions(
+                &parser,
+                &uri,
+                &rope,
+                params.range,
+                &params.context.diagnostics,
+            ));
+        }
+        Ok(Some(actions))
+    }
+
+    async fn goto_definition(
+        &mut self,
+        params: lsp_ty::GotoDefinitionParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::GotoDefinitionResponse>> {
+        let doc = params.text_document_position_params;
+        let state = self.state.lock().await;
+        let index = state.symbol_index_for(&doc.text_document.uri);
+        Ok(index.and_then(|index| {
+            let at = index.symbol_at(&doc.text_document.uri, doc.position)?;
+            let locations = index.definition(&at);
+            if locations.is_empty() {
+                None
+            } else {
+                Some(lsp_ty::GotoDefinitionResponse::Array(locations))
+            }
+        }))
+    }
+
+    async fn references(
+        &mut self,
+        params: lsp_ty::ReferenceParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::Location>>> {
+        let doc = params.text_document_position;
+        let state = self.state.lock().await;
+        let index = state.symbol_index_for(&doc.text_document.uri);
+        Ok(index.and_then(|index| {
+            let at = index.symbol_at(&doc.text_document.uri, doc.position)?;
+            Some(index.references(at.name(), params.context.include_declaration))
+        }))
+    }
 
-                    extensions.insert(extension.to_os_string(), parser_info.clone());
+    async fn prepare_rename(
+        &mut self,
+        params: lsp_ty::TextDocumentPositionParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::PrepareRenameResponse>> {
+        let state = self.state.lock().await;
+        let index = state.symbol_index_for(&params.text_document.uri);
+        Ok(index.and_then(|index| {
+            let at = index.symbol_at(&params.text_document.uri, params.position)?;
+            let rope = if params.text_document.uri == index.y.uri {
+                &index.y.rope
+            } else {
+                &index.l.as_ref()?.rope
+            };
+            Some(lsp_ty::PrepareRenameResponse::Range(
+                documents::span_to_range(rope, at.name_span()),
+            ))
+        }))
+    }
+
+    async fn rename(
+        &mut self,
+        params: lsp_ty::RenameParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::WorkspaceEdit>> {
+        let doc = params.text_document_position;
+        let state = self.state.lock().await;
+        let index = if let Some(index) = state.symbol_index_for(&doc.text_document.uri) {
+            index
+        } else {
+            return Ok(None);
+        };
+        let at = if let Some(at) = index.symbol_at(&doc.text_document.uri, doc.position) {
+            at
+        } else {
+            return Ok(None);
+        };
+        index
+            .rename(&at, &params.new_name)
+            .map(Some)
+            .map_err(|reason| jsonrpc::Error {
+                code: jsonrpc::ErrorCode::InvalidParams,
+                message: Cow::from(reason),
+                data: Some(serde_json::Value::String(params.new_name)),
+            })
+    }
+
+    async fn completion(
+        &mut self,
+        params: lsp_ty::CompletionParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::CompletionResponse>> {
+        let doc = params.text_document_position;
+        let state = self.state.lock().await;
+        let index = state.symbol_index_for(&doc.text_document.uri);
+        Ok(index
+            .filter(|index| index.y.uri == doc.text_document.uri)
+            .map(|index| lsp_ty::CompletionResponse::Array(index.completions(doc.position))))
+    }
+
+    async fn hover(
+        &mut self,
+        params: lsp_ty::HoverParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::Hover>> {
+        let doc = params.text_document_position_params;
+        let state = self.state.lock().await;
+        let index = if let Some(index) = state.symbol_index_for(&doc.text_document.uri) {
+            index
+        } else {
+            return Ok(None);
+        };
+        let parser = doc
+            .text_document
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| state.parser_of_file(&path))
+            .and_then(|parser_info| state.parsing_state.get(parser_info.id()))
+            .and_then(ParsingState::built);
+        Ok(index
+            .symbol_at(&doc.text_document.uri, doc.position)
+            .and_then(|at| index.hover(&at, parser.as_ref())))
+    }
+
+    async fn document_symbol(
+        &mut self,
+        params: lsp_ty::DocumentSymbolParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+        let state = self.state.lock().await;
+        Ok(state
+            .symbol_index_for(&uri)
+            .and_then(|index| index.document_symbols(&uri))
+            .map(lsp_ty::DocumentSymbolResponse::Nested))
+    }
+
+    async fn symbol(
+        &mut self,
+        params: lsp_ty::WorkspaceSymbolParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::SymbolInformation>>> {
+        let state = self.state.lock().await;
+        let mut symbols = Vec::new();
+        for (workspace_path, WorkspaceCfg { workspace }) in &state.toml {
+            let toml_path = workspace_path.join("nimbleparse.toml");
+            for parser in workspace.parsers.get_ref() {
+                let y_path = workspace_path.join(parser.y_file.get_ref());
+                if let Some(index) = state
+                    .find_parser_info(&y_path)
+                    .and_then(|parser_info| state.symbol_index(parser_info))
+                {
+                    let container = format!(
+                        "{} ({})",
+                        parser.y_file.get_ref().display(),
+                        toml_path.display()
+                    );
+                    symbols.extend(index.workspace_symbols(&params.query, &container));
                 }
             }
         }
+        symbols.sort_by(|(a_score, a), (b_score, b)| {
+            a_score.cmp(b_score).then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(Some(symbols.into_iter().map(|(_, sym)| sym).collect()))
+    }
 
-        self.client
-            .log_message(lsp_ty::MessageType::LOG, "initialized!")
-            .await;
+    async fn prepare_call_hierarchy(
+        &mut self,
+        params: lsp_ty::CallHierarchyPrepareParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::CallHierarchyItem>>> {
+        let doc = params.text_document_position_params;
+        let state = self.state.lock().await;
+        let index = state.symbol_index_for(&doc.text_document.uri);
+        Ok(index.and_then(|index| {
+            let at = index.symbol_at(&doc.text_document.uri, doc.position)?;
+            Some(vec![index.call_hierarchy_item(at.name())?])
+        }))
     }
 
-    async fn shutdown(&mut self) -> jsonrpc::Result<()> {
-        Ok(())
+    async fn incoming_calls(
+        &mut self,
+        params: lsp_ty::CallHierarchyIncomingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::CallHierarchyIncomingCall>>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .symbol_index_for(&params.item.uri)
+            .map(|index| index.incoming_calls(&params.item.name)))
+    }
+
+    async fn outgoing_calls(
+        &mut self,
+        params: lsp_ty::CallHierarchyOutgoingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::CallHierarchyOutgoingCall>>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .symbol_index_for(&params.item.uri)
+            .map(|index| index.outgoing_calls(&params.item.name)))
+    }
+
+    async fn formatting(
+        &mut self,
+        params: lsp_ty::DocumentFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::TextEdit>>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .grammar_contents(&params.text_document.uri)
+            .and_then(|rope| {
+                let syntax = syntax::YaccSyntax::new(&rope.to_string());
+                format::format_edits(&rope, &syntax, None)
+            }))
+    }
+
+    async fn range_formatting(
+        &mut self,
+        params: lsp_ty::DocumentRangeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::TextEdit>>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .grammar_contents(&params.text_document.uri)
+            .and_then(|rope| {
+                let syntax = syntax::YaccSyntax::new(&rope.to_string());
+                format::format_edits(&rope, &syntax, Some(params.range))
+            }))
+    }
+
+    async fn on_type_formatting(
+        &mut self,
+        params: lsp_ty::DocumentOnTypeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<lsp_ty::TextEdit>>> {
+        let doc = params.text_document_position;
+        let state = self.state.lock().await;
+        Ok(state.grammar_contents(&doc.text_document.uri).map(|rope| {
+            let syntax = syntax::YaccSyntax::new(&rope.to_string());
+            format::on_type_edits(&rope, &syntax, doc.position)
+        }))
+    }
+
+    async fn semantic_tokens_full(
+        &mut self,
+        params: lsp_ty::SemanticTokensParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::SemanticTokensResult>> {
+        let uri = params.text_document.uri;
+        let state = self.state.lock().await;
+        Ok(state
+            .symbol_index_for(&uri)
+            .and_then(|index| semantic_tokens::semantic_tokens(&index, &uri, None))
+            .map(lsp_ty::SemanticTokensResult::Tokens))
+    }
+
+    async fn semantic_tokens_range(
+        &mut self,
+        params: lsp_ty::SemanticTokensRangeParams,
+    ) -> jsonrpc::Result<Option<lsp_ty::SemanticTokensRangeResult>> {
+        let uri = params.text_document.uri;
+        let state = self.state.lock().await;
+        Ok(state
+            .symbol_index_for(&uri)
+            .and_then(|index| semantic_tokens::semantic_tokens(&index, &uri, Some(params.range)))
+            .map(lsp_ty::SemanticTokensRangeResult::Tokens))
+    }
+
+    async fn did_save(&mut self, params: lsp_ty::DidSaveTextDocumentParams) {
+        let path = if let Ok(path) = params.text_document.uri.to_file_path() {
+            path
+        } else {
+            return;
+        };
+        let state = self.state.lock().await;
+        let config_of = state.config_of(&path);
+        let workspaces = state.grammar_workspaces(&path);
+        let client_monitor = state.client_monitor;
+        drop(state);
+        if let Some(workspace_path) = config_of {
+            // Clients which watch files report the save through `did_change_watched_files`.
+            if !client_monitor {
+                self.reload_workspace(&workspace_path).await;
+            }
+            return;
+        }
+        // Generators may read the grammar from disk, so are rerun when it is saved.
+        for workspace_path in workspaces {
+            self.run_generators(&workspace_path, true).await;
+        }
+    }
+
+    async fn did_change_watched_files(&mut self, params: lsp_ty::DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            let path = if let Ok(path) = change.uri.to_file_path() {
+                path
+            } else {
+                continue;
+            };
+            let state = self.state.lock().await;
+            let config_of = state.config_of(&path);
+            let workspaces = state.grammar_workspaces(&path);
+            // Open documents are kept up to date by `did_change` instead, and other inputs are
+            // only parsed while open.
+            let is_open = state.documents.contains_key(&path);
+            let is_test = state.test_of(&path).is_some();
+            drop(state);
+            if let Some(workspace_path) = config_of {
+                self.reload_workspace(&workspace_path).await;
+            } else if !is_open && (is_test || !workspaces.is_empty()) {
+                self.file_changed(&path).await;
+                for workspace_path in workspaces {
+                    self.run_generators(&workspace_path, true).await;
+                }
+            }
+        }
+    }
+
+    async fn did_close(&mut self, params: lsp_ty::DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if let Ok(path) = uri.to_file_path() {
+            let mut state = self.state.lock().await;
+            state.documents.remove(&path);
+            // Inputs are only parsed while open, so their diagnostics go stale once closed.
+            // Tests are the exception, they are run whether open or not.
+            let is_grammar = state
+                .extensions
+                .values()
+                .any(|parser_info| parser_info.is_parser(&path) || parser_info.is_lexer(&path));
+            let is_test = state.test_of(&path).is_some();
+            let is_input = state.parser_for(&path).is_some();
+            drop(state);
+            if is_grammar || is_test {
+                // Any unsaved changes were discarded, so rebuild from the file on disk.
+                self.file_changed(&path).await;
+            } else if is_input {
+                self.client.publish_diagnostics(uri, Vec::new(), None).await;
+            }
+        }
     }
 }
 
 fn run_server_arg() -> std::result::Result<(), ServerError> {
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_io()
+        .enable_time()
         .build()?;
     rt.block_on(async {
         log::set_max_level(log::LevelFilter::Info);
         let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
         let (service, socket) = tower_lsp::LspService::build(|client| Backend {
-            state: tokio::sync::Mutex::new(State {
+            state: std::sync::Arc::new(tokio::sync::Mutex::new(State {
                 toml: std::collections::HashMap::new(),
-                warned_needs_restart: false,
                 client_monitor: false,
+                create_files: false,
                 extensions: std::collections::HashMap::new(),
                 parsing_state: Vec::new(),
-            }),
+                documents: std::collections::HashMap::new(),
+                generated: std::collections::HashMap::new(),
+                test_outcomes: std::collections::HashMap::new(),
+                symbol_indexes: std::sync::Mutex::new(std::collections::HashMap::new()),
+            })),
             client,
         })
         .custom_method(
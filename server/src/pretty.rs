@@ -0,0 +1,91 @@
+//! Plain text renderings of parsers, for the client to show as read only documents.
+use crate::documents::byte_to_position;
+use crate::{BuiltParser, StateGraphPretty};
+use cfgrammar::{Span, TIdx};
+use lrlex::LexerDef as _;
+use lrpar::Lexeme as _;
+
+type Node = lrpar::Node<lrlex::DefaultLexeme<u32>, u32>;
+
+/// The span from the first to the last lexeme of a node, `None` if it derived nothing.
+fn node_span(node: &Node) -> Option<Span> {
+    match node {
+        Node::Term { lexeme } => Some(lexeme.span()),
+        Node::Nonterm { nodes, .. } => {
+            let start = nodes.iter().find_map(node_span)?;
+            let end = nodes.iter().rev().find_map(node_span)?;
+            Some(Span::new(start.start(), end.end()))
+        }
+    }
+}
+
+fn pp_span(rope: &ropey::Rope, span: Span) -> String {
+    let start = byte_to_position(rope, span.start());
+    let end = byte_to_position(rope, span.end());
+    format!(
+        "{}:{}..{}:{}",
+        start.line + 1,
+        start.character + 1,
+        end.line + 1,
+        end.character + 1
+    )
+}
+
+/// Parses `rope` and pretty prints the generic parse tree, followed by any errors.
+///
+/// Each line is a rule name or a token name with its lexeme, along with its source range.
+pub fn generic_tree(parser: &BuiltParser, rope: &ropey::Rope) -> String {
+    let src = rope.to_string();
+    let lexer = parser.lexerdef.lexer(&src);
+    let (tree, errs) = parser.rtpb.parse_generictree(&lexer);
+    let mut out = String::new();
+    if let Some(tree) = &tree {
+        let mut stack = vec![(tree, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            out.push_str(&" ".repeat(depth));
+            match node {
+                Node::Term { lexeme } => {
+                    let span = lexeme.span();
+                    let name = parser.grm.token_name(TIdx(lexeme.tok_id())).unwrap_or("$");
+                    out.push_str(&format!(
+                        "{} {:?} {}",
+                        name,
+                        &src[span.start()..span.end()],
+                        pp_span(rope, span)
+                    ));
+                    if lexeme.faulty() {
+                        out.push_str(" (inserted by error recovery)");
+                    }
+                }
+                Node::Nonterm { ridx, nodes } => {
+                    out.push_str(parser.grm.rule_name_str(*ridx));
+                    if let Some(span) = node_span(node) {
+                        out.push(' ');
+                        out.push_str(&pp_span(rope, span));
+                    }
+                    stack.extend(nodes.iter().rev().map(|child| (child, depth + 1)));
+                }
+            }
+            out.push('\n');
+        }
+    }
+    if !errs.is_empty() {
+        out.push_str("\nErrors:\n");
+        for err in &errs {
+            out.push_str(&err.pp(&lexer, &|tidx| parser.grm.token_epp(tidx)));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Pretty prints the LR(1) states of the parser, using the printers of `lrtable`.
+pub fn state_graph(parser: &BuiltParser, pretty: &StateGraphPretty) -> String {
+    let (grm, sgraph) = (parser.grm, parser.sgraph);
+    match pretty {
+        StateGraphPretty::CoreStates => sgraph.pp_core_states(grm),
+        StateGraphPretty::ClosedStates => sgraph.pp_closed_states(grm),
+        StateGraphPretty::CoreEdges => sgraph.pp(grm, true),
+        StateGraphPretty::AllEdges => sgraph.pp(grm, false),
+    }
+}
@@ -0,0 +1,187 @@
+//! Railroad diagrams of a grammar, rendered as a single SVG document.
+//!
+//! Each rule is drawn as a choice between its productions, each production a sequence
+//! of boxes. Rules are laid out one below another in a group with the id `rule-<name>`,
+//! and the box of each nonterminal links to its rule's group.
+use crate::grammar::user_rules;
+use crate::syntax::YaccSyntax;
+use cfgrammar::{yacc::YaccGrammar, RIdx, Symbol};
+use std::fmt::Write as _;
+
+const CHAR_WIDTH: usize = 8;
+const BOX_HEIGHT: usize = 24;
+const BOX_PADDING: usize = 10;
+const GAP: usize = 16;
+const RAIL: usize = 16;
+const ROW_HEIGHT: usize = 44;
+const TITLE_HEIGHT: usize = 30;
+const MARGIN: usize = 10;
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn label(grm: &YaccGrammar, sym: Symbol<u32>) -> String {
+    match sym {
+        Symbol::Rule(ridx) => grm.rule_name_str(ridx).to_string(),
+        Symbol::Token(tidx) => format!("'{}'", grm.token_name(tidx).unwrap_or("$")),
+    }
+}
+
+fn box_width(label: &str) -> usize {
+    label.chars().count() * CHAR_WIDTH + 2 * BOX_PADDING
+}
+
+fn row_width(grm: &YaccGrammar, syms: &[Symbol<u32>]) -> usize {
+    let boxes = syms
+        .iter()
+        .map(|sym| box_width(&label(grm, *sym)))
+        .sum::<usize>();
+    boxes + GAP * syms.len().saturating_sub(1)
+}
+
+/// Draws the rule `ridx` with its top left corner at `y`, returning its height.
+///
+/// A production's precedence is labelled `%prec` only where the source has a `%prec`,
+/// otherwise it was inferred from the production's tokens.
+fn rule_diagram(
+    grm: &YaccGrammar,
+    syntax: &YaccSyntax,
+    ridx: RIdx<u32>,
+    y: usize,
+    out: &mut String,
+) -> usize {
+    let name = grm.rule_name_str(ridx);
+    let prods = grm.rule_to_prods(ridx);
+    let precs = syntax
+        .alts_of(name)
+        .map(|alt| alt.prec.map(|idx| syntax.symbols[idx].name.as_str()))
+        .collect::<Vec<_>>();
+    let widths = prods
+        .iter()
+        .map(|pidx| row_width(grm, grm.prod(*pidx)))
+        .collect::<Vec<_>>();
+    let content_width = widths.iter().copied().max().unwrap_or(0).max(GAP);
+    let left_rail = MARGIN + 2 * RAIL;
+    let content_x = left_rail + RAIL;
+    let right_rail = content_x + content_width + RAIL;
+    let first_cy = TITLE_HEIGHT + BOX_HEIGHT / 2;
+    let last_cy = first_cy + (prods.len().saturating_sub(1)) * ROW_HEIGHT;
+    let height = TITLE_HEIGHT + prods.len().max(1) * ROW_HEIGHT;
+
+    let _ = writeln!(
+        out,
+        r#"<g id="rule-{name}" transform="translate(0,{y})"><text class="title" x="{MARGIN}" y="18">{}</text>"#,
+        escape(name)
+    );
+    // Entry and exit markers, and the rails joining every production.
+    let _ = writeln!(
+        out,
+        r#"<circle cx="{}" cy="{first_cy}" r="4"/><path d="M{} {first_cy}H{left_rail}V{last_cy}M{right_rail} {first_cy}V{last_cy}M{right_rail} {first_cy}h{}"/><circle cx="{}" cy="{first_cy}" r="4"/>"#,
+        MARGIN + 4,
+        MARGIN + 8,
+        RAIL,
+        right_rail + RAIL + 4,
+    );
+    for (row, (pidx, width)) in prods.iter().zip(widths.iter()).enumerate() {
+        let cy = first_cy + row * ROW_HEIGHT;
+        let top = cy - BOX_HEIGHT / 2;
+        let mut x = content_x;
+        let _ = writeln!(out, r#"<path d="M{left_rail} {cy}H{content_x}"/>"#);
+        for (i, sym) in grm.prod(*pidx).iter().enumerate() {
+            if i > 0 {
+                let _ = writeln!(out, r#"<path d="M{x} {cy}h{GAP}"/>"#);
+                x += GAP;
+            }
+            let text = label(grm, *sym);
+            let w = box_width(&text);
+            let text_x = x + w / 2;
+            let text_y = cy + 4;
+            match sym {
+                Symbol::Rule(target) => {
+                    let _ = writeln!(
+                        out,
+                        r##"<a href="#rule-{target}" xlink:href="#rule-{target}"><rect class="nonterminal" x="{x}" y="{top}" width="{w}" height="{BOX_HEIGHT}"/><text x="{text_x}" y="{text_y}">{}</text></a>"##,
+                        escape(&text),
+                        target = grm.rule_name_str(*target),
+                    );
+                }
+                Symbol::Token(_) => {
+                    let _ = writeln!(
+                        out,
+                        r#"<rect class="terminal" x="{x}" y="{top}" width="{w}" height="{BOX_HEIGHT}" rx="10"/><text x="{text_x}" y="{text_y}">{}</text>"#,
+                        escape(&text),
+                    );
+                }
+            }
+            x += w;
+        }
+        let _ = writeln!(
+            out,
+            r#"<path d="M{} {cy}H{right_rail}"/>"#,
+            content_x + width
+        );
+        if let Some(prec) = grm.prod_precedence(*pidx) {
+            let text = match precs.get(row).copied().flatten() {
+                Some(token) => format!("%prec {token} ({:?} {})", prec.kind, prec.level),
+                None => format!("precedence {:?} {} (inferred)", prec.kind, prec.level),
+            };
+            let _ = writeln!(
+                out,
+                r#"<text class="prec" x="{content_x}" y="{}">{}</text>"#,
+                cy + BOX_HEIGHT / 2 + 12,
+                escape(&text)
+            );
+        }
+    }
+    out.push_str("</g>\n");
+    height
+}
+
+/// Renders a diagram of every rule in the grammar, whose source is scanned as `syntax`.
+pub fn railroad_svg(grm: &YaccGrammar, syntax: &YaccSyntax) -> String {
+    let mut body = String::new();
+    let mut y = MARGIN;
+    let mut width = 0;
+    for ridx in user_rules(grm) {
+        y += rule_diagram(grm, syntax, ridx, y, &mut body);
+        let content = grm
+            .rule_to_prods(ridx)
+            .iter()
+            .map(|pidx| row_width(grm, grm.prod(*pidx)))
+            .max()
+            .unwrap_or(0);
+        width = width.max(MARGIN + 4 * RAIL + content + 2 * RAIL + MARGIN);
+    }
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{width}" height="{y}" font-family="monospace" font-size="12">"#
+    );
+    out.push_str(
+        "<style>\
+         path { fill: none; stroke: black; stroke-width: 1.5; }\
+         rect { stroke: black; stroke-width: 1.5; }\
+         rect.terminal { fill: #ddf5dd; }\
+         rect.nonterminal { fill: #dde8f5; }\
+         a rect.nonterminal:hover { fill: #b8d0f0; }\
+         text { text-anchor: middle; }\
+         text.title { text-anchor: start; font-weight: bold; font-size: 14px; }\
+         text.prec { text-anchor: start; font-size: 10px; fill: #555; }\
+         </style>\n",
+    );
+    out.push_str(&body);
+    out.push_str("</svg>\n");
+    out
+}
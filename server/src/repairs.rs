@@ -0,0 +1,186 @@
+//! Quick fixes which apply the repair sequences found by CPCT+ error recovery.
+use crate::documents::{byte_to_position, span_to_range};
+use crate::BuiltParser;
+use lrlex::LexerDef as _;
+use lrpar::{Lexeme as _, NonStreamingLexer as _};
+use tower_lsp::lsp_types as lsp_ty;
+
+type ParseRepair = lrpar::ParseRepair<lrlex::DefaultLexeme<u32>, u32>;
+
+fn overlaps(a: lsp_ty::Range, b: lsp_ty::Range) -> bool {
+    let pos = |p: lsp_ty::Position| (p.line, p.character);
+    pos(a.start) <= pos(b.end) && pos(b.start) <= pos(a.end)
+}
+
+/// Describes a repair sequence as e.g. `Insert "INT", Delete ")"`, omitting trailing shifts.
+fn describe(parser: &BuiltParser, src: &str, repairs: &[ParseRepair]) -> String {
+    let last_edit = repairs
+        .iter()
+        .rposition(|repair| !matches!(repair, lrpar::ParseRepair::Shift(_)))
+        .map_or(0, |idx| idx + 1);
+    repairs[..last_edit]
+        .iter()
+        .map(|repair| match repair {
+            lrpar::ParseRepair::Insert(tidx) => {
+                let name = parser
+                    .grm
+                    .token_epp(*tidx)
+                    .or_else(|| parser.grm.token_name(*tidx))
+                    .unwrap_or("$");
+                format!("Insert \"{name}\"")
+            }
+            lrpar::ParseRepair::Delete(lexeme) => {
+                format!(
+                    "Delete \"{}\"",
+                    &src[lexeme.span().start()..lexeme.span().end()]
+                )
+            }
+            lrpar::ParseRepair::Shift(lexeme) => {
+                format!(
+                    "Shift \"{}\"",
+                    &src[lexeme.span().start()..lexeme.span().end()]
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The text to insert for the token `tidx`, its token name if that lexes back to the token
+/// by itself. This holds for quoted tokens, but not for tokens such as `INT` or `ID`.
+fn insert_text<'a>(parser: &BuiltParser<'a>, tidx: cfgrammar::TIdx<u32>) -> Option<&'a str> {
+    let text = parser.grm.token_name(tidx)?;
+    let lexer = parser.lexerdef.lexer(text);
+    let mut lexemes = lexer.iter();
+    match (lexemes.next(), lexemes.next()) {
+        (Some(Ok(lexeme)), None)
+            if usize::try_from(lexeme.tok_id()).ok() == Some(usize::from(tidx))
+                && lexeme.span().len() == text.len() =>
+        {
+            Some(text)
+        }
+        _ => None,
+    }
+}
+
+fn is_word(c: Option<char>) -> bool {
+    c.map_or(false, |c| c.is_alphanumeric() || c == '_')
+}
+
+/// Joins `text` onto `insertion`, separating them only where they would otherwise run together.
+fn push_separated(insertion: &mut String, text: &str) {
+    if is_word(insertion.chars().last()) && is_word(text.chars().next()) {
+        insertion.push(' ');
+    }
+    insertion.push_str(text);
+}
+
+/// An edit inserting `text` at the byte offset `pos` of `src`, separated from its neighbours
+/// where they would otherwise run together.
+fn insert_edit(rope: &ropey::Rope, src: &str, pos: usize, text: &str) -> lsp_ty::TextEdit {
+    let mut new_text = String::new();
+    if is_word(src[..pos].chars().last()) && is_word(text.chars().next()) {
+        new_text.push(' ');
+    }
+    new_text.push_str(text);
+    if is_word(text.chars().last()) && is_word(src[pos..].chars().next()) {
+        new_text.push(' ');
+    }
+    let at = byte_to_position(rope, pos);
+    lsp_ty::TextEdit {
+        range: lsp_ty::Range::new(at, at),
+        new_text,
+    }
+}
+
+/// Converts a repair sequence starting at the lexeme `start` into text edits.
+///
+/// Returns `None` when an inserted token has no text which lexes as that token.
+fn repair_edits(
+    parser: &BuiltParser,
+    rope: &ropey::Rope,
+    src: &str,
+    start: usize,
+    repairs: &[ParseRepair],
+) -> Option<Vec<lsp_ty::TextEdit>> {
+    let mut pos = start;
+    let mut edits = Vec::new();
+    // Consecutive insertions at the same position become a single edit.
+    let mut insertion = String::new();
+    for repair in repairs {
+        if !matches!(repair, lrpar::ParseRepair::Insert(_)) && !insertion.is_empty() {
+            edits.push(insert_edit(rope, src, pos, &insertion));
+            insertion.clear();
+        }
+        match repair {
+            lrpar::ParseRepair::Insert(tidx) => {
+                push_separated(&mut insertion, insert_text(parser, *tidx)?);
+            }
+            lrpar::ParseRepair::Delete(lexeme) => {
+                edits.push(lsp_ty::TextEdit {
+                    range: span_to_range(rope, lexeme.span()),
+                    new_text: String::new(),
+                });
+                pos = lexeme.span().end();
+            }
+            lrpar::ParseRepair::Shift(lexeme) => pos = lexeme.span().end(),
+        }
+    }
+    if !insertion.is_empty() {
+        edits.push(insert_edit(rope, src, pos, &insertion));
+    }
+    Some(edits)
+}
+
+/// Offers every repair sequence of the parse errors within `range` as a quick fix.
+pub fn repair_actions(
+    parser: &BuiltParser,
+    uri: &lsp_ty::Url,
+    rope: &ropey::Rope,
+    range: lsp_ty::Range,
+    diagnostics: &[lsp_ty::Diagnostic],
+) -> lsp_ty::CodeActionResponse {
+    let src = rope.to_string();
+    let lexer = parser.lexerdef.lexer(&src);
+    let (_, errs) = parser.rtpb.parse_generictree(&lexer);
+    let mut actions = Vec::new();
+    for err in errs {
+        if let lrpar::LexParseError::ParseError(err) = err {
+            let err_range = span_to_range(rope, err.lexeme().span());
+            if !overlaps(err_range, range) {
+                continue;
+            }
+            let fixes = diagnostics
+                .iter()
+                .filter(|diag| diag.range == err_range)
+                .cloned()
+                .collect::<Vec<_>>();
+            for repairs in err.repairs() {
+                let edits =
+                    match repair_edits(parser, rope, &src, err.lexeme().span().start(), repairs) {
+                        Some(edits) if !edits.is_empty() => edits,
+                        _ => continue,
+                    };
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(uri.clone(), edits);
+                actions.push(lsp_ty::CodeActionOrCommand::CodeAction(
+                    lsp_ty::CodeAction {
+                        title: describe(parser, &src, repairs),
+                        kind: Some(lsp_ty::CodeActionKind::QUICKFIX),
+                        diagnostics: if fixes.is_empty() {
+                            None
+                        } else {
+                            Some(fixes.clone())
+                        },
+                        edit: Some(lsp_ty::WorkspaceEdit {
+                            changes: Some(changes),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ));
+            }
+        }
+    }
+    actions
+}
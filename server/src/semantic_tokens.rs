@@ -0,0 +1,206 @@
+//! Semantic tokens for `.y` and `.l` files.
+//!
+//! Symbols are classified against the grammar, so a token with no rule in the lexer
+//! is shown differently from one which has. Action code is reported as `macro`, the
+//! closest of the standard types to embedded code.
+use crate::documents::byte_to_position;
+use crate::symbols::SymbolIndex;
+use crate::syntax::{ItemKind, SymbolRole};
+use cfgrammar::Span;
+use tower_lsp::lsp_types as lsp_ty;
+
+const NONTERMINAL: u32 = 0;
+const TERMINAL: u32 = 1;
+const STRING: u32 = 2;
+const UNDEFINED: u32 = 3;
+const DIRECTIVE: u32 = 4;
+const ACTION: u32 = 5;
+const ACTION_TYPE: u32 = 6;
+const REGEX: u32 = 7;
+const COMMENT: u32 = 8;
+const NUMBER: u32 = 9;
+const PUNCT: u32 = 10;
+
+const DECLARATION: u32 = 1 << 0;
+const DEFINITION: u32 = 1 << 1;
+/// Quoted literal tokens are a `STRING` with this modifier, telling them apart from `%epp` names.
+const LITERAL: u32 = 1 << 2;
+
+pub fn legend() -> lsp_ty::SemanticTokensLegend {
+    lsp_ty::SemanticTokensLegend {
+        // In the order of the constants above.
+        token_types: vec![
+            lsp_ty::SemanticTokenType::FUNCTION,
+            lsp_ty::SemanticTokenType::ENUM_MEMBER,
+            lsp_ty::SemanticTokenType::STRING,
+            lsp_ty::SemanticTokenType::VARIABLE,
+            lsp_ty::SemanticTokenType::KEYWORD,
+            lsp_ty::SemanticTokenType::MACRO,
+            lsp_ty::SemanticTokenType::TYPE,
+            lsp_ty::SemanticTokenType::REGEXP,
+            lsp_ty::SemanticTokenType::COMMENT,
+            lsp_ty::SemanticTokenType::NUMBER,
+            lsp_ty::SemanticTokenType::OPERATOR,
+        ],
+        token_modifiers: vec![
+            lsp_ty::SemanticTokenModifier::DECLARATION,
+            lsp_ty::SemanticTokenModifier::DEFINITION,
+            lsp_ty::SemanticTokenModifier::new("literal"),
+        ],
+    }
+}
+
+/// A token before it is split into lines and delta encoded.
+struct RawToken {
+    span: Span,
+    token_type: u32,
+    modifiers: u32,
+}
+
+fn yacc_tokens(index: &SymbolIndex) -> Vec<RawToken> {
+    let syntax = &index.y_syntax;
+    let mut tokens = Vec::with_capacity(syntax.items.len());
+    for item in &syntax.items {
+        let (token_type, modifiers) = match item.kind {
+            ItemKind::Comment => (COMMENT, 0),
+            ItemKind::SectionMarker | ItemKind::Directive => (DIRECTIVE, 0),
+            ItemKind::Action | ItemKind::Code => (ACTION, 0),
+            ItemKind::ActionType => (ACTION_TYPE, 0),
+            ItemKind::Str => (STRING, 0),
+            ItemKind::Number => (NUMBER, 0),
+            ItemKind::Punct => (PUNCT, 0),
+            ItemKind::DeclSymbol | ItemKind::RuleName | ItemKind::Symbol => {
+                let sym = match syntax
+                    .symbols
+                    .binary_search_by_key(&item.span.start(), |sym| sym.span.start())
+                {
+                    Ok(i) => &syntax.symbols[i],
+                    Err(_) => continue,
+                };
+                let (token_type, literal) = if index.is_rule(&sym.name) {
+                    (NONTERMINAL, 0)
+                } else if index.lexer_rule(&sym.name).is_none() {
+                    (UNDEFINED, 0)
+                } else if sym.quoted {
+                    (STRING, LITERAL)
+                } else {
+                    (TERMINAL, 0)
+                };
+                let modifiers = match sym.role {
+                    SymbolRole::RuleDef(_) => DEFINITION,
+                    SymbolRole::Decl(_) => DECLARATION,
+                    SymbolRole::Production { .. } | SymbolRole::Prec { .. } => 0,
+                };
+                (token_type, modifiers | literal)
+            }
+        };
+        tokens.push(RawToken {
+            span: item.span,
+            token_type,
+            modifiers,
+        });
+    }
+    tokens
+}
+
+fn lex_tokens(index: &SymbolIndex) -> Vec<RawToken> {
+    let syntax = &index.l_syntax;
+    let mut tokens = Vec::new();
+    if let Some(start) = syntax.rules_start {
+        tokens.push(RawToken {
+            span: Span::new(start, start + 2),
+            token_type: DIRECTIVE,
+            modifiers: 0,
+        });
+    }
+    for rule in &syntax.rules {
+        tokens.push(RawToken {
+            span: rule.regex.1,
+            token_type: REGEX,
+            modifiers: 0,
+        });
+        if let Some((_, span)) = &rule.name {
+            tokens.push(RawToken {
+                span: *span,
+                token_type: TERMINAL,
+                modifiers: DEFINITION,
+            });
+        }
+    }
+    tokens
+}
+
+/// Splits tokens at line breaks, then delta encodes those within `range`.
+fn encode(
+    rope: &ropey::Rope,
+    tokens: &[RawToken],
+    range: Option<lsp_ty::Range>,
+) -> Vec<lsp_ty::SemanticToken> {
+    let pos = |p: lsp_ty::Position| (p.line, p.character);
+    let mut data = Vec::with_capacity(tokens.len());
+    let (mut prev_line, mut prev_start) = (0, 0);
+    for token in tokens {
+        let start = byte_to_position(rope, token.span.start());
+        let end = byte_to_position(rope, token.span.end());
+        if let Some(range) = range {
+            if pos(end) < pos(range.start) || pos(start) > pos(range.end) {
+                continue;
+            }
+        }
+        for line in start.line..=end.line {
+            let from = if line == start.line {
+                start.character
+            } else {
+                0
+            };
+            let to = if line == end.line {
+                end.character
+            } else {
+                let text = rope.line(line as usize);
+                let newline = text
+                    .chars()
+                    .rev()
+                    .take_while(|c| *c == '\n' || *c == '\r')
+                    .count();
+                (text.len_utf16_cu() - newline) as u32
+            };
+            if to <= from {
+                continue;
+            }
+            let delta_line = line - prev_line;
+            let delta_start = if delta_line == 0 {
+                from - prev_start
+            } else {
+                from
+            };
+            data.push(lsp_ty::SemanticToken {
+                delta_line,
+                delta_start,
+                length: to - from,
+                token_type: token.token_type,
+                token_modifiers_bitset: token.modifiers,
+            });
+            prev_line = line;
+            prev_start = from;
+        }
+    }
+    data
+}
+
+/// The semantic tokens of `uri`, which must be the `.y` or `.l` file of the index.
+pub fn semantic_tokens(
+    index: &SymbolIndex,
+    uri: &lsp_ty::Url,
+    range: Option<lsp_ty::Range>,
+) -> Option<lsp_ty::SemanticTokens> {
+    let data = if *uri == index.y.uri {
+        encode(&index.y.rope, &yacc_tokens(index), range)
+    } else {
+        let l = index.l.as_ref().filter(|l| l.uri == *uri)?;
+        encode(&l.rope, &lex_tokens(index), range)
+    };
+    Some(lsp_ty::SemanticTokens {
+        result_id: None,
+        data,
+    })
+}
@@ -0,0 +1,687 @@
+//! An index of where the rules and tokens of a parser are defined and used,
+//! across its `.y` and `.l` files.
+use crate::documents::{position_to_byte, span_to_range};
+use crate::grammar;
+use crate::syntax::{self, ItemKind, LexRuleSyntax, LexSyntax, SymbolRef, SymbolRole, YaccSyntax};
+use cfgrammar::{yacc::YaccGrammar, Span};
+use lrlex::LexerDef as _;
+use tower_lsp::lsp_types as lsp_ty;
+
+pub struct Source {
+    pub uri: lsp_ty::Url,
+    pub rope: ropey::Rope,
+}
+
+impl Source {
+    pub fn location(&self, span: Span) -> lsp_ty::Location {
+        lsp_ty::Location {
+            uri: self.uri.clone(),
+            range: span_to_range(&self.rope, span),
+        }
+    }
+}
+
+/// Matches `query` against `candidate` as a case insensitive subsequence.
+///
+/// Returns how far apart the matched characters are, so lower scores are closer matches
+/// and a prefix of `candidate` scores 0.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut last = None;
+    let mut chars = candidate
+        .chars()
+        .map(|c| c.to_ascii_lowercase())
+        .enumerate();
+    for q in query.chars().map(|c| c.to_ascii_lowercase()) {
+        let (i, _) = chars.find(|(_, c)| *c == q)?;
+        score += match last {
+            Some(last) => i - last - 1,
+            None => i,
+        };
+        last = Some(i);
+    }
+    Some(score)
+}
+
+pub struct SymbolIndex {
+    pub y: Source,
+    pub y_syntax: YaccSyntax,
+    pub l: Option<Source>,
+    pub l_syntax: LexSyntax,
+    /// `None` while the grammar doesn't build, definitions then come from the syntax alone.
+    pub grm: Option<YaccGrammar>,
+    pub lexerdef: Option<crate::LexerDef>,
+}
+
+/// The symbol under the cursor, in either file of a parser.
+pub enum SymbolAt<'a> {
+    Yacc(&'a SymbolRef),
+    Lex(&'a LexRuleSyntax),
+}
+
+impl<'a> SymbolAt<'a> {
+    pub fn name(&self) -> &'a str {
+        match self {
+            SymbolAt::Yacc(sym) => &sym.name,
+            SymbolAt::Lex(rule) => rule.name.as_ref().map_or("", |(name, _)| name),
+        }
+    }
+
+    /// The span of the name under the cursor, excluding any quotes.
+    pub fn name_span(&self) -> Span {
+        match self {
+            SymbolAt::Yacc(sym) => sym.name_span(),
+            SymbolAt::Lex(rule) => rule.name.as_ref().map_or(rule.span, |(_, span)| *span),
+        }
+    }
+}
+
+impl SymbolIndex {
+    pub fn new(
+        y: Source,
+        l: Option<Source>,
+        grm: Option<YaccGrammar>,
+        lexerdef: Option<crate::LexerDef>,
+    ) -> Self {
+        let y_syntax = YaccSyntax::new(&y.rope.to_string());
+        let l_syntax = l
+            .as_ref()
+            .map(|l| LexSyntax::new(&l.rope.to_string()))
+            .unwrap_or_default();
+        SymbolIndex {
+            y,
+            y_syntax,
+            l,
+            l_syntax,
+            grm,
+            lexerdef,
+        }
+    }
+
+    pub fn symbol_at(&self, uri: &lsp_ty::Url, pos: lsp_ty::Position) -> Option<SymbolAt<'_>> {
+        if *uri == self.y.uri {
+            let offset = position_to_byte(&self.y.rope, pos);
+            self.y_syntax.symbol_at(offset).map(SymbolAt::Yacc)
+        } else {
+            let l = self.l.as_ref().filter(|l| l.uri == *uri)?;
+            let offset = position_to_byte(&l.rope, pos);
+            self.l_syntax.rule_name_at(offset).map(SymbolAt::Lex)
+        }
+    }
+
+    pub fn is_rule(&self, name: &str) -> bool {
+        match &self.grm {
+            Some(grm) => grm.rule_idx(name).is_some(),
+            None => self.y_syntax.rules.iter().any(|rule| rule.name == name),
+        }
+    }
+
+    pub fn is_token(&self, name: &str) -> bool {
+        self.grm
+            .as_ref()
+            .map_or(false, |grm| grm.token_idx(name).is_some())
+            || self.l_syntax.rule(name).is_some()
+            || self
+                .y_occurrences(name, |role| matches!(role, SymbolRole::Decl(_)))
+                .next()
+                .is_some()
+    }
+
+    /// The definition of the rule `name` in the `.y` file.
+    pub fn rule_definition(&self, name: &str) -> Option<lsp_ty::Location> {
+        let span = match &self.grm {
+            Some(grm) => grm.rule_idx(name).map(|ridx| grm.rule_name_span(ridx)),
+            None => self
+                .y_syntax
+                .rules
+                .iter()
+                .find(|rule| rule.name == name)
+                .map(|rule| rule.name_span),
+        }?;
+        Some(self.y.location(span))
+    }
+
+    /// The rule of the `.l` file which produces the token `name`.
+    pub fn lexer_rule(&self, name: &str) -> Option<lsp_ty::Location> {
+        let l = self.l.as_ref()?;
+        let span = self
+            .lexerdef
+            .as_ref()
+            .and_then(|lexerdef| lexerdef.get_rule_by_name(name))
+            .map(|rule| rule.name_span)
+            .or_else(|| {
+                self.l_syntax
+                    .rule(name)
+                    .and_then(|rule| rule.name.as_ref())
+                    .map(|(_, span)| *span)
+            })?;
+        Some(l.location(span))
+    }
+
+    /// The uses of `name` in the `.y` file for which `filter` holds, in order.
+    pub fn y_occurrences<'a>(
+        &'a self,
+        name: &'a str,
+        filter: impl Fn(&SymbolRole) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a SymbolRef> + 'a {
+        self.y_syntax
+            .symbols
+            .iter()
+            .filter(move |sym| sym.name == name && filter(&sym.role))
+    }
+
+    /// The declarations of the token `name` with the directive `directive`, e.g. `token`.
+    pub fn declarations<'a>(
+        &'a self,
+        name: &'a str,
+        directive: &'a str,
+    ) -> impl Iterator<Item = &'a SymbolRef> + 'a {
+        self.y_occurrences(name, move |role| match role {
+            SymbolRole::Decl(decl) => self.y_syntax.decls[*decl].directive == directive,
+            _ => false,
+        })
+    }
+
+    /// Where the symbol under the cursor is defined.
+    ///
+    /// For a rule this is its definition. For a token it is the rule in the `.l` file producing it,
+    /// or failing that its `%token` declaration. For a rule of the `.l` file it is the first use of
+    /// the token in the `.y` file.
+    pub fn definition(&self, at: &SymbolAt) -> Vec<lsp_ty::Location> {
+        let name = at.name();
+        match at {
+            SymbolAt::Yacc(_) if self.is_rule(name) => {
+                self.rule_definition(name).into_iter().collect()
+            }
+            SymbolAt::Yacc(_) => self
+                .lexer_rule(name)
+                .into_iter()
+                .chain(
+                    self.declarations(name, "token")
+                        .map(|sym| self.y.location(sym.name_span())),
+                )
+                .take(1)
+                .collect(),
+            SymbolAt::Lex(_) => self
+                .y_occurrences(name, |role| matches!(role, SymbolRole::Production { .. }))
+                .chain(self.y_occurrences(name, |_| true))
+                .take(1)
+                .map(|sym| self.y.location(sym.name_span()))
+                .collect(),
+        }
+    }
+
+    /// Every reference to the symbol `name` across both files.
+    ///
+    /// The definition of a rule and `%token` declarations are only included with
+    /// `include_declaration`, the `.l` rule producing a token is always included.
+    pub fn references(&self, name: &str, include_declaration: bool) -> Vec<lsp_ty::Location> {
+        let is_declaration = |role: &SymbolRole| match role {
+            SymbolRole::RuleDef(_) => true,
+            SymbolRole::Decl(decl) => self.y_syntax.decls[*decl].directive == "token",
+            _ => false,
+        };
+        let mut locations = self
+            .y_occurrences(name, |role| include_declaration || !is_declaration(role))
+            .map(|sym| self.y.location(sym.name_span()))
+            .collect::<Vec<_>>();
+        if !self.is_rule(name) {
+            locations.extend(self.lexer_rule(name));
+        }
+        locations
+    }
+
+    /// Renames the symbol under the cursor in both files.
+    ///
+    /// Renaming a token renames its uses and declarations in the `.y` file and its rule in the
+    /// `.l` file. Renaming a rule renames its definition, its uses, and `%start`. Uses which are
+    /// not quoted gain quotes when the new name isn't an identifier.
+    pub fn rename(&self, at: &SymbolAt, new_name: &str) -> Result<lsp_ty::WorkspaceEdit, String> {
+        let name = at.name();
+        let is_rule = self.is_rule(name);
+        if new_name.is_empty() {
+            return Err("The new name is empty".to_string());
+        }
+        if new_name != name && (self.is_rule(new_name) || self.is_token(new_name)) {
+            return Err(format!("'{new_name}' is already the name of a symbol"));
+        }
+        if is_rule && !syntax::is_identifier(new_name) {
+            return Err(format!("'{new_name}' is not a valid rule name"));
+        }
+        if new_name.contains('\n') || (new_name.contains('\'') && new_name.contains('"')) {
+            return Err(format!("'{new_name}' cannot be quoted"));
+        }
+
+        let mut y_edits = Vec::new();
+        for sym in self.y_occurrences(name, |_| true) {
+            let quote = sym
+                .quoted
+                .then(|| self.y.rope.byte(sym.span.start()) as char);
+            let edit = match quote {
+                Some(quote) if new_name.contains(quote) => {
+                    let quote = if quote == '"' { '\'' } else { '"' };
+                    (sym.span, format!("{quote}{new_name}{quote}"))
+                }
+                Some(_) => (sym.name_span(), new_name.to_string()),
+                None if syntax::is_identifier(new_name) => (sym.span, new_name.to_string()),
+                None => {
+                    let quote = if new_name.contains('\'') { '"' } else { '\'' };
+                    (sym.span, format!("{quote}{new_name}{quote}"))
+                }
+            };
+            y_edits.push(lsp_ty::TextEdit {
+                range: span_to_range(&self.y.rope, edit.0),
+                new_text: edit.1,
+            });
+        }
+
+        let mut changes = std::collections::HashMap::new();
+        changes.insert(self.y.uri.clone(), y_edits);
+        if !is_rule {
+            if let (Some(l), Some((_, span))) = (
+                &self.l,
+                self.l_syntax.rule(name).and_then(|rule| rule.name.as_ref()),
+            ) {
+                if new_name.contains('"') {
+                    return Err(format!("'{new_name}' cannot be a lexer rule name"));
+                }
+                changes.insert(
+                    l.uri.clone(),
+                    vec![lsp_ty::TextEdit {
+                        range: span_to_range(&l.rope, *span),
+                        new_text: new_name.to_string(),
+                    }],
+                );
+            }
+        }
+        Ok(lsp_ty::WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        })
+    }
+
+    /// Completes rule names, and the token names of the `.l` file, at `pos` in the `.y` file.
+    ///
+    /// Nothing is offered inside comments, action code, or strings. Items are sorted
+    /// by how close their nearest use is to `pos`.
+    pub fn completions(&self, pos: lsp_ty::Position) -> Vec<lsp_ty::CompletionItem> {
+        let offset = position_to_byte(&self.y.rope, pos);
+        let in_code = self.y_syntax.items.iter().any(|item| {
+            matches!(
+                item.kind,
+                ItemKind::Comment
+                    | ItemKind::Action
+                    | ItemKind::ActionType
+                    | ItemKind::Code
+                    | ItemKind::Str
+            ) && item.span.start() < offset
+                && offset < item.span.end()
+        });
+        if in_code {
+            return Vec::new();
+        }
+        let distance = |name: &str| {
+            self.y_syntax
+                .symbols
+                .iter()
+                .filter(|sym| sym.name == name)
+                .map(|sym| sym.span.start().abs_diff(offset))
+                .min()
+                .unwrap_or(usize::MAX)
+        };
+        let item = |name: &str, kind, detail: String| lsp_ty::CompletionItem {
+            label: name.to_string(),
+            kind: Some(kind),
+            detail: Some(detail),
+            sort_text: Some(format!("{:020}{name}", distance(name))),
+            insert_text: if syntax::is_identifier(name) {
+                None
+            } else {
+                Some(format!("'{name}'"))
+            },
+            ..Default::default()
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let mut items = Vec::new();
+        for rule in &self.y_syntax.rules {
+            if seen.insert(rule.name.as_str()) {
+                let prods = self.y_syntax.alts_of(&rule.name).count();
+                let detail = if prods == 1 {
+                    "rule, 1 production".to_string()
+                } else {
+                    format!("rule, {prods} productions")
+                };
+                items.push(item(&rule.name, lsp_ty::CompletionItemKind::CLASS, detail));
+            }
+        }
+        for rule in &self.l_syntax.rules {
+            if let Some((name, _)) = &rule.name {
+                if seen.insert(name.as_str()) {
+                    let detail = format!("token, {}", rule.regex.0);
+                    items.push(item(name, lsp_ty::CompletionItemKind::CONSTANT, detail));
+                }
+            }
+        }
+        items
+    }
+
+    /// The outline of `uri`, which must be the `.y` or `.l` file of the index.
+    ///
+    /// In the `.y` file each rule has its productions as children. In the `.l` file each
+    /// named rule has its regex as detail, rules which discard their input are left out.
+    #[allow(deprecated)]
+    pub fn document_symbols(&self, uri: &lsp_ty::Url) -> Option<Vec<lsp_ty::DocumentSymbol>> {
+        let symbol =
+            |name: String, detail, kind, range, selection_range, children| lsp_ty::DocumentSymbol {
+                name,
+                detail,
+                kind,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range,
+                children,
+            };
+        if *uri == self.y.uri {
+            let src = self.y.rope.to_string();
+            let rope = &self.y.rope;
+            let text = |sym: usize| {
+                let span = self.y_syntax.symbols[sym].span;
+                &src[span.start()..span.end()]
+            };
+            let rules = self
+                .y_syntax
+                .rules
+                .iter()
+                .map(|rule| {
+                    let name_range = span_to_range(rope, rule.name_span);
+                    let prods = rule
+                        .alts
+                        .iter()
+                        .map(|alt| {
+                            let mut name = alt
+                                .symbols
+                                .iter()
+                                .map(|sym| text(*sym))
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            if let Some(prec) = alt.prec {
+                                name = format!("{name} %prec {}", text(prec));
+                            }
+                            let range = if alt.span.start() < alt.span.end() {
+                                span_to_range(rope, alt.span)
+                            } else {
+                                name_range
+                            };
+                            let name = match name.trim_start() {
+                                "" => "(empty)".to_string(),
+                                name => name.to_string(),
+                            };
+                            symbol(name, None, lsp_ty::SymbolKind::FIELD, range, range, None)
+                        })
+                        .collect();
+                    let action_type = rule.action_type.map(|span| {
+                        src[span.start()..span.end()]
+                            .trim_start_matches("->")
+                            .trim()
+                            .to_string()
+                    });
+                    symbol(
+                        rule.name.clone(),
+                        action_type,
+                        lsp_ty::SymbolKind::CLASS,
+                        span_to_range(rope, rule.span),
+                        name_range,
+                        Some(prods),
+                    )
+                })
+                .collect();
+            Some(rules)
+        } else {
+            let l = self.l.as_ref().filter(|l| l.uri == *uri)?;
+            let rules = self
+                .l_syntax
+                .rules
+                .iter()
+                .filter_map(|rule| {
+                    let (name, span) = rule.name.as_ref()?;
+                    Some(symbol(
+                        name.clone(),
+                        Some(rule.regex.0.clone()),
+                        lsp_ty::SymbolKind::CONSTANT,
+                        span_to_range(&l.rope, rule.span),
+                        span_to_range(&l.rope, *span),
+                        None,
+                    ))
+                })
+                .collect();
+            Some(rules)
+        }
+    }
+
+    /// The rules and tokens whose names fuzzily match `query`, with their scores.
+    ///
+    /// Tokens are located at their rule in the `.l` file, or failing that at their first
+    /// occurrence in the `.y` file.
+    #[allow(deprecated)]
+    pub fn workspace_symbols(
+        &self,
+        query: &str,
+        container: &str,
+    ) -> Vec<(usize, lsp_ty::SymbolInformation)> {
+        let mut seen = std::collections::HashSet::new();
+        let mut symbols = Vec::new();
+        let mut push = |name: &str, kind, location: Option<lsp_ty::Location>| {
+            if let (Some(score), Some(location)) = (fuzzy_score(query, name), location) {
+                if seen.insert(name.to_string()) {
+                    symbols.push((
+                        score,
+                        lsp_ty::SymbolInformation {
+                            name: name.to_string(),
+                            kind,
+                            tags: None,
+                            deprecated: None,
+                            location,
+                            container_name: Some(container.to_string()),
+                        },
+                    ));
+                }
+            }
+        };
+        for rule in &self.y_syntax.rules {
+            push(
+                &rule.name,
+                lsp_ty::SymbolKind::CLASS,
+                Some(self.y.location(rule.name_span)),
+            );
+        }
+        for rule in &self.l_syntax.rules {
+            if let Some((name, _)) = &rule.name {
+                push(name, lsp_ty::SymbolKind::CONSTANT, self.lexer_rule(name));
+            }
+        }
+        for sym in &self.y_syntax.symbols {
+            if !self.is_rule(&sym.name) {
+                push(
+                    &sym.name,
+                    lsp_ty::SymbolKind::CONSTANT,
+                    Some(self.y.location(sym.name_span())),
+                );
+            }
+        }
+        symbols
+    }
+
+    /// A call hierarchy item for the rule `name`, at its first definition.
+    pub fn call_hierarchy_item(&self, name: &str) -> Option<lsp_ty::CallHierarchyItem> {
+        let rule = self.y_syntax.rules.iter().find(|rule| rule.name == name)?;
+        Some(lsp_ty::CallHierarchyItem {
+            name: rule.name.clone(),
+            kind: lsp_ty::SymbolKind::CLASS,
+            tags: None,
+            detail: None,
+            uri: self.y.uri.clone(),
+            range: span_to_range(&self.y.rope, rule.span),
+            selection_range: span_to_range(&self.y.rope, rule.name_span),
+            data: None,
+        })
+    }
+
+    /// Groups the uses of symbols in productions by `key`, in order of first use.
+    fn calls(
+        &self,
+        key: impl Fn(&SymbolRef, &syntax::RuleSyntax) -> Option<String>,
+    ) -> Vec<(lsp_ty::CallHierarchyItem, Vec<lsp_ty::Range>)> {
+        let mut calls: Vec<(String, Vec<lsp_ty::Range>)> = Vec::new();
+        for sym in &self.y_syntax.symbols {
+            if let SymbolRole::Production { rule, .. } = sym.role {
+                if let Some(key) = key(sym, &self.y_syntax.rules[rule]) {
+                    let range = span_to_range(&self.y.rope, sym.span);
+                    match calls.iter_mut().find(|(name, _)| *name == key) {
+                        Some((_, ranges)) => ranges.push(range),
+                        None => calls.push((key, vec![range])),
+                    }
+                }
+            }
+        }
+        calls
+            .into_iter()
+            .filter_map(|(name, ranges)| Some((self.call_hierarchy_item(&name)?, ranges)))
+            .collect()
+    }
+
+    /// The rules with productions referencing the rule `name`, with the range of each reference.
+    pub fn incoming_calls(&self, name: &str) -> Vec<lsp_ty::CallHierarchyIncomingCall> {
+        self.calls(|sym, rule| (sym.name == name).then(|| rule.name.clone()))
+            .into_iter()
+            .map(|(from, from_ranges)| lsp_ty::CallHierarchyIncomingCall { from, from_ranges })
+            .collect()
+    }
+
+    /// The rules referenced by the productions of the rule `name`, with the range of each reference.
+    pub fn outgoing_calls(&self, name: &str) -> Vec<lsp_ty::CallHierarchyOutgoingCall> {
+        self.calls(|sym, rule| {
+            (rule.name == name && self.is_rule(&sym.name)).then(|| sym.name.clone())
+        })
+        .into_iter()
+        .map(|(to, from_ranges)| lsp_ty::CallHierarchyOutgoingCall { to, from_ranges })
+        .collect()
+    }
+
+    fn rule_hover(
+        &self,
+        grm: &YaccGrammar,
+        name: &str,
+        parser: Option<&crate::BuiltParser>,
+    ) -> Option<String> {
+        let ridx = grm.rule_idx(name)?;
+        let mut s = format!("**rule** `{name}`\n\n```yacc\n");
+        for (i, pidx) in grm.rule_to_prods(ridx).iter().enumerate() {
+            let prod = grammar::pp_prod(grm, *pidx);
+            if i == 0 {
+                s.push_str(&prod);
+            } else {
+                // Align alternatives under the `:` of the first production.
+                let body = prod.split_once(':').map_or("", |(_, body)| body);
+                s.push_str(&format!("\n{}|{body}", " ".repeat(name.len())));
+            }
+        }
+        s.push_str("\n```\n\n");
+        let firsts = grm.firsts();
+        let first_set = grm
+            .iter_tidxs()
+            .filter(|tidx| firsts.is_set(ridx, *tidx))
+            .map(|tidx| format!("`'{}'`", grm.token_name(tidx).unwrap_or("$")))
+            .collect::<Vec<_>>();
+        s.push_str(&format!(
+            "Nullable: {}\n\nFIRST: {}\n",
+            if firsts.is_epsilon_set(ridx) {
+                "yes"
+            } else {
+                "no"
+            },
+            first_set.join(", ")
+        ));
+        // The state graph belongs to the last successful build, so look the rule up again.
+        if let Some(parser) = parser {
+            if let Some(built_ridx) = parser.grm.rule_idx(name) {
+                let prods = parser.grm.rule_to_prods(built_ridx);
+                let mut states = 0;
+                let mut total = 0;
+                for stidx in parser.sgraph.iter_stidxs() {
+                    total += 1;
+                    if parser
+                        .sgraph
+                        .core_state(stidx)
+                        .items
+                        .keys()
+                        .any(|(pidx, _)| prods.contains(pidx))
+                    {
+                        states += 1;
+                    }
+                }
+                s.push_str(&format!(
+                    "\nIn the kernel of {states} of {total} LR states\n"
+                ));
+            }
+        }
+        Some(s)
+    }
+
+    fn token_hover(&self, name: &str) -> String {
+        let mut s = format!("**token** `{name}`\n");
+        let regex = self
+            .lexerdef
+            .as_ref()
+            .and_then(|lexerdef| lexerdef.get_rule_by_name(name))
+            .map(|rule| rule.re_str.clone())
+            .or_else(|| self.l_syntax.rule(name).map(|rule| rule.regex.0.clone()));
+        match regex {
+            Some(regex) => s.push_str(&format!("\n```\n{regex}\n```\n")),
+            None => s.push_str("\nNo rule in the lexer produces this token.\n"),
+        }
+        if let Some((grm, tidx)) = self
+            .grm
+            .as_ref()
+            .and_then(|grm| Some((grm, grm.token_idx(name)?)))
+        {
+            if let Some(prec) = grm.token_precedence(tidx) {
+                s.push_str(&format!("\nPrecedence: {} {:?}\n", prec.level, prec.kind));
+            }
+            if let Some(epp) = grm.token_epp(tidx).filter(|epp| *epp != name) {
+                s.push_str(&format!("\nPretty name: {epp:?}\n"));
+            }
+        }
+        s
+    }
+
+    /// Describes the symbol under the cursor.
+    ///
+    /// For a rule this is its productions, whether it is nullable, its FIRST set and how many
+    /// states of the built parser it appears in. For a token it is its regex, its precedence and
+    /// its `%epp` pretty name.
+    pub fn hover(
+        &self,
+        at: &SymbolAt,
+        parser: Option<&crate::BuiltParser>,
+    ) -> Option<lsp_ty::Hover> {
+        let name = at.name();
+        let value = if self.is_rule(name) {
+            self.rule_hover(self.grm.as_ref()?, name, parser)?
+        } else {
+            self.token_hover(name)
+        };
+        let rope = match at {
+            SymbolAt::Yacc(_) => &self.y.rope,
+            SymbolAt::Lex(_) => &self.l.as_ref()?.rope,
+        };
+        Some(lsp_ty::Hover {
+            contents: lsp_ty::HoverContents::Markup(lsp_ty::MarkupContent {
+                kind: lsp_ty::MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(span_to_range(rope, at.name_span())),
+        })
+    }
+}
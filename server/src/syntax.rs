@@ -0,0 +1,723 @@
+//! An error tolerant scanner for the surface syntax of `.y` and `.l` files.
+//!
+//! grmtools only keeps spans for the definitions of rules and tokens, editor features also need
+//! the uses of each symbol, the extent of each production, and where action code and comments are.
+//! This recovers those from source text which may not currently build.
+use cfgrammar::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Comment,
+    /// `%%` between sections.
+    SectionMarker,
+    /// A directive such as `%token` or `%prec`, the span includes the `%`.
+    Directive,
+    /// A symbol named by a declaration.
+    DeclSymbol,
+    /// The name of the rule being defined.
+    RuleName,
+    /// A symbol used in a production or in `%prec`.
+    Symbol,
+    /// Action code, including its braces.
+    Action,
+    /// The `-> Type` of a rule.
+    ActionType,
+    /// Rust code which isn't an action, `%parse-param`, `%actiontype` and the programs section.
+    Code,
+    /// A string argument, such as the pretty name of `%epp`.
+    Str,
+    Number,
+    /// One of `:`, `|` or `;`.
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolRole {
+    /// Named by the declaration at this index of `YaccSyntax::decls`.
+    Decl(usize),
+    /// The definition of the rule at this index of `YaccSyntax::rules`.
+    RuleDef(usize),
+    /// Used in the given alternative of a rule.
+    Production { rule: usize, alt: usize },
+    /// The `%prec` of the given alternative of a rule.
+    Prec { rule: usize, alt: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolRef {
+    /// The name of the symbol, without any quotes.
+    pub name: String,
+    /// The span of the symbol including any quotes.
+    pub span: Span,
+    pub quoted: bool,
+    pub role: SymbolRole,
+}
+
+impl SymbolRef {
+    /// The span of just the name, excluding any quotes.
+    ///
+    /// An unterminated quote has no closing quote to exclude, so the name runs from the opening one.
+    pub fn name_span(&self) -> Span {
+        if self.quoted {
+            let start = self.span.start() + 1;
+            Span::new(start, start + self.name.len())
+        } else {
+            self.span
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeclSyntax {
+    /// The directive without its leading `%`.
+    pub directive: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltSyntax {
+    /// From the first to the last item of the alternative, empty for an empty alternative.
+    pub span: Span,
+    pub symbols: Vec<usize>,
+    pub prec: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSyntax {
+    pub name: String,
+    pub name_span: Span,
+    /// From the rule name to its terminating `;`.
+    pub span: Span,
+    pub action_type: Option<Span>,
+    pub alts: Vec<AltSyntax>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct YaccSyntax {
+    /// Every item of the file in order.
+    pub items: Vec<Item>,
+    /// Every occurrence of a symbol in order, `AltSyntax::symbols` and `prec` index into this.
+    pub symbols: Vec<SymbolRef>,
+    pub decls: Vec<DeclSyntax>,
+    pub rules: Vec<RuleSyntax>,
+}
+
+fn is_ident_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Whether `s` can be written in a `.y` file without quotes.
+pub fn is_identifier(s: &str) -> bool {
+    s.as_bytes().first().map_or(false, |c| is_ident_start(*c)) && s.bytes().all(is_ident_char)
+}
+
+struct Scanner<'a> {
+    src: &'a str,
+    pos: usize,
+    syntax: YaccSyntax,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + offset).copied()
+    }
+
+    fn at(&self, s: &str) -> bool {
+        self.src[self.pos..].starts_with(s)
+    }
+
+    fn item(&mut self, kind: ItemKind, start: usize, end: usize) {
+        self.syntax.items.push(Item {
+            kind,
+            span: Span::new(start, end),
+        });
+    }
+
+    /// Advances past a single, possibly multibyte, character.
+    fn bump(&mut self) {
+        let len = self.src[self.pos..]
+            .chars()
+            .next()
+            .map_or(0, |c| c.len_utf8());
+        self.pos += len;
+    }
+
+    fn skip_line(&mut self) {
+        self.pos = self.src[self.pos..]
+            .find('\n')
+            .map_or(self.src.len(), |off| self.pos + off);
+    }
+
+    fn skip_block_comment(&mut self) {
+        self.pos = self.src[self.pos + 2..]
+            .find("*/")
+            .map_or(self.src.len(), |off| self.pos + 2 + off + 2);
+    }
+
+    /// Skips whitespace and comments, recording the comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(b'/') if self.peek_at(1) == Some(b'/') => {
+                    let start = self.pos;
+                    self.skip_line();
+                    self.item(ItemKind::Comment, start, self.pos);
+                }
+                Some(b'/') if self.peek_at(1) == Some(b'*') => {
+                    let start = self.pos;
+                    self.skip_block_comment();
+                    self.item(ItemKind::Comment, start, self.pos);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Skips only spaces and tabs, so declarations can stop at the end of their line.
+    fn skip_inline_space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn ident(&mut self) -> Option<(String, Span)> {
+        if !self.peek().map_or(false, is_ident_start) {
+            return None;
+        }
+        let start = self.pos;
+        while self.peek().map_or(false, is_ident_char) {
+            self.pos += 1;
+        }
+        Some((
+            self.src[start..self.pos].to_string(),
+            Span::new(start, self.pos),
+        ))
+    }
+
+    fn directive_name(&mut self) -> (String, Span) {
+        let start = self.pos;
+        self.pos += 1;
+        while self.peek().map_or(false, |c| is_ident_char(c) || c == b'-') {
+            self.pos += 1;
+        }
+        (
+            self.src[start + 1..self.pos].to_string(),
+            Span::new(start, self.pos),
+        )
+    }
+
+    /// Scans a quoted string, returning its contents and the span including the quotes.
+    fn quoted(&mut self) -> Option<(String, Span)> {
+        let quote = self.peek().filter(|c| *c == b'\'' || *c == b'"')?;
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(c) = self.peek() {
+            if c == quote {
+                self.pos += 1;
+                let contents = self.src[start + 1..self.pos - 1].to_string();
+                return Some((contents, Span::new(start, self.pos)));
+            } else if c == b'\n' {
+                break;
+            }
+            self.bump();
+        }
+        // Unterminated, treat everything up to the end of the line as the contents.
+        Some((
+            self.src[start + 1..self.pos].to_string(),
+            Span::new(start, self.pos),
+        ))
+    }
+
+    fn symbol(&mut self, role: SymbolRole) -> Option<usize> {
+        let (name, span, quoted) = if let Some((name, span)) = self.ident() {
+            (name, span, false)
+        } else {
+            let (name, span) = self.quoted()?;
+            (name, span, true)
+        };
+        let kind = match role {
+            SymbolRole::Decl(_) => ItemKind::DeclSymbol,
+            SymbolRole::RuleDef(_) => ItemKind::RuleName,
+            SymbolRole::Production { .. } | SymbolRole::Prec { .. } => ItemKind::Symbol,
+        };
+        self.item(kind, span.start(), span.end());
+        self.syntax.symbols.push(SymbolRef {
+            name,
+            span,
+            quoted,
+            role,
+        });
+        Some(self.syntax.symbols.len() - 1)
+    }
+
+    /// Skips a rust string or char literal, or a lifetime, starting at the current quote.
+    fn skip_rust_literal(&mut self) {
+        let bytes = self.src.as_bytes();
+        if bytes[self.pos] == b'"' {
+            self.pos += 1;
+            while let Some(c) = self.peek() {
+                self.pos += 1;
+                match c {
+                    b'\\' => self.pos += 1,
+                    b'"' => return,
+                    _ => (),
+                }
+            }
+            self.pos = self.pos.min(self.src.len());
+        } else if self.peek_at(1) == Some(b'\\') {
+            // An escaped char literal such as '\n', '\'' or '\u{1F600}', the escaped character
+            // may be multibyte.
+            self.pos += 2;
+            self.bump();
+            let from = self.pos;
+            self.pos = self.src[from..]
+                .find('\'')
+                .map_or(self.src.len(), |off| from + off + 1);
+        } else {
+            let after = self.src[self.pos + 1..]
+                .chars()
+                .next()
+                .map_or(0, |c| c.len_utf8());
+            if self.src.as_bytes().get(self.pos + 1 + after) == Some(&b'\'') {
+                self.pos += 1 + after + 1;
+            } else {
+                // A lifetime.
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips balanced braces starting at the current `{`.
+    fn skip_braces(&mut self) {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            match c {
+                b'{' => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                b'}' => {
+                    self.pos += 1;
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                b'"' | b'\'' => self.skip_rust_literal(),
+                b'/' if self.peek_at(1) == Some(b'/') => self.skip_line(),
+                b'/' if self.peek_at(1) == Some(b'*') => self.skip_block_comment(),
+                _ => self.bump(),
+            }
+        }
+    }
+
+    fn declarations(&mut self) {
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => return,
+                Some(b'%') if self.peek_at(1) == Some(b'%') => return,
+                Some(b'%') => self.declaration(),
+                Some(_) => self.bump(),
+            }
+        }
+    }
+
+    fn declaration(&mut self) {
+        let (directive, directive_span) = self.directive_name();
+        self.item(
+            ItemKind::Directive,
+            directive_span.start(),
+            directive_span.end(),
+        );
+        let decl = self.syntax.decls.len();
+        match directive.as_str() {
+            "actiontype" | "parse-param" | "type" => {
+                self.skip_inline_space();
+                let start = self.pos;
+                self.skip_line();
+                let end = start + self.src[start..self.pos].trim_end().len();
+                if end > start {
+                    self.item(ItemKind::Code, start, end);
+                }
+            }
+            "expect" | "expect-rr" => {
+                self.skip_inline_space();
+                let start = self.pos;
+                while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                if self.pos > start {
+                    self.item(ItemKind::Number, start, self.pos);
+                }
+            }
+            "epp" => {
+                self.skip_trivia();
+                self.symbol(SymbolRole::Decl(decl));
+                self.skip_trivia();
+                if let Some((_, span)) = self.quoted() {
+                    self.item(ItemKind::Str, span.start(), span.end());
+                }
+            }
+            _ => loop {
+                self.skip_trivia();
+                if self.peek() == Some(b'%') || self.symbol(SymbolRole::Decl(decl)).is_none() {
+                    break;
+                }
+            },
+        }
+        self.syntax.decls.push(DeclSyntax { directive });
+    }
+
+    /// Whether the upcoming text looks like the start of a rule, used to recover from a missing `;`.
+    fn at_rule_start(&self) -> bool {
+        let rest = &self.src[self.pos..];
+        let ident_len = rest.bytes().take_while(|c| is_ident_char(*c)).count();
+        if ident_len == 0 || !is_ident_start(rest.as_bytes()[0]) {
+            return false;
+        }
+        let after = rest[ident_len..].trim_start();
+        (after.starts_with(':') && !after.starts_with("::")) || after.starts_with("->")
+    }
+
+    fn rules(&mut self) {
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => return,
+                Some(b'%') if self.peek_at(1) == Some(b'%') => {
+                    self.item(ItemKind::SectionMarker, self.pos, self.pos + 2);
+                    self.pos += 2;
+                    let start = self.pos;
+                    let code = &self.src[start..];
+                    let code_start = start + (code.len() - code.trim_start().len());
+                    let code_end = start + code.trim_end().len();
+                    if code_end > code_start {
+                        self.item(ItemKind::Code, code_start, code_end);
+                    }
+                    self.pos = self.src.len();
+                    return;
+                }
+                Some(c) if is_ident_start(c) => self.rule(),
+                Some(_) => self.bump(),
+            }
+        }
+    }
+
+    fn rule(&mut self) {
+        let rule = self.syntax.rules.len();
+        let name_idx = self.symbol(SymbolRole::RuleDef(rule)).unwrap();
+        let name_span = self.syntax.symbols[name_idx].span;
+        let mut action_type = None;
+        self.skip_trivia();
+        if self.at("->") {
+            let start = self.pos;
+            self.pos += 2;
+            while let Some(c) = self.peek() {
+                if c == b':' && self.peek_at(1) == Some(b':') {
+                    self.pos += 2;
+                } else if c == b':' || c == b';' {
+                    break;
+                } else {
+                    self.bump();
+                }
+            }
+            let end = start + self.src[start..self.pos].trim_end().len();
+            self.item(ItemKind::ActionType, start, end);
+            action_type = Some(Span::new(start, end));
+            self.skip_trivia();
+        }
+        self.syntax.rules.push(RuleSyntax {
+            name: self.syntax.symbols[name_idx].name.clone(),
+            name_span,
+            span: name_span,
+            action_type,
+            alts: Vec::new(),
+        });
+        if self.peek() == Some(b':') {
+            self.item(ItemKind::Punct, self.pos, self.pos + 1);
+            self.pos += 1;
+        }
+
+        let mut alt = AltSyntax {
+            span: Span::new(self.pos, self.pos),
+            symbols: Vec::new(),
+            prec: None,
+        };
+        let mut alt_start = None;
+        let mut alt_end = self.pos;
+        let end = loop {
+            self.skip_trivia();
+            let pos = self.pos;
+            let alt_idx = self.syntax.rules[rule].alts.len();
+            match self.peek() {
+                None => break alt_end,
+                Some(b'%') if self.peek_at(1) == Some(b'%') => break alt_end,
+                Some(b'|') | Some(b';') => {
+                    let c = self.peek();
+                    self.item(ItemKind::Punct, pos, pos + 1);
+                    self.pos += 1;
+                    let start = alt_start.take().unwrap_or(pos);
+                    alt.span = Span::new(start, if start == pos { pos } else { alt_end });
+                    let next = AltSyntax {
+                        span: Span::new(self.pos, self.pos),
+                        symbols: Vec::new(),
+                        prec: None,
+                    };
+                    self.syntax.rules[rule]
+                        .alts
+                        .push(std::mem::replace(&mut alt, next));
+                    alt_end = self.pos;
+                    if c == Some(b';') {
+                        self.syntax.rules[rule].span = Span::new(name_span.start(), self.pos);
+                        return;
+                    }
+                }
+                Some(b'{') => {
+                    self.skip_braces();
+                    self.item(ItemKind::Action, pos, self.pos);
+                    alt_start.get_or_insert(pos);
+                    alt_end = self.pos;
+                }
+                Some(b'%') => {
+                    let (directive, span) = self.directive_name();
+                    self.item(ItemKind::Directive, span.start(), span.end());
+                    alt_start.get_or_insert(pos);
+                    alt_end = self.pos;
+                    if directive == "prec" {
+                        self.skip_trivia();
+                        let role = SymbolRole::Prec { rule, alt: alt_idx };
+                        if let Some(idx) = self.symbol(role) {
+                            alt.prec = Some(idx);
+                            alt_end = self.pos;
+                        }
+                    }
+                }
+                Some(c) if is_ident_start(c) && self.at_rule_start() => break alt_end,
+                Some(_) => {
+                    let role = SymbolRole::Production { rule, alt: alt_idx };
+                    if let Some(idx) = self.symbol(role) {
+                        alt.symbols.push(idx);
+                        alt_start.get_or_insert(pos);
+                        alt_end = self.pos;
+                    } else {
+                        self.bump();
+                    }
+                }
+            }
+        };
+        // The rule is missing its `;`, keep what we have.
+        let start = alt_start.unwrap_or(alt_end);
+        alt.span = Span::new(start, alt_end);
+        self.syntax.rules[rule].alts.push(alt);
+        self.syntax.rules[rule].span = Span::new(name_span.start(), end);
+    }
+}
+
+impl YaccSyntax {
+    pub fn new(src: &str) -> Self {
+        let mut scanner = Scanner {
+            src,
+            pos: 0,
+            syntax: YaccSyntax::default(),
+        };
+        scanner.declarations();
+        if scanner.at("%%") {
+            scanner.item(ItemKind::SectionMarker, scanner.pos, scanner.pos + 2);
+            scanner.pos += 2;
+            scanner.rules();
+        }
+        scanner.syntax
+    }
+
+    /// The alternatives of every definition of the rule `name`, in source order.
+    ///
+    /// These are in the same order as `YaccGrammar::rule_to_prods`.
+    pub fn alts_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a AltSyntax> + 'a {
+        self.rules
+            .iter()
+            .filter(move |rule| rule.name == name)
+            .flat_map(|rule| rule.alts.iter())
+    }
+
+    /// Returns the symbol whose span contains `offset`.
+    pub fn symbol_at(&self, offset: usize) -> Option<&SymbolRef> {
+        self.symbols
+            .iter()
+            .find(|sym| sym.span.start() <= offset && offset <= sym.span.end())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexRuleSyntax {
+    /// The rule name without quotes, `None` for rules which discard their input with `;`.
+    pub name: Option<(String, Span)>,
+    pub regex: (String, Span),
+    /// The whole line of the rule, excluding its line break.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LexSyntax {
+    pub rules_start: Option<usize>,
+    pub rules: Vec<LexRuleSyntax>,
+}
+
+impl LexSyntax {
+    pub fn new(src: &str) -> Self {
+        let mut syntax = LexSyntax::default();
+        let mut offset = 0;
+        let mut lines = src.split_inclusive('\n');
+        for line in lines.by_ref() {
+            let start = offset;
+            offset += line.len();
+            if line.trim_end() == "%%" {
+                syntax.rules_start = Some(start);
+                break;
+            }
+        }
+        if syntax.rules_start.is_none() {
+            return syntax;
+        }
+        for line in lines {
+            let start = offset;
+            offset += line.len();
+            let content = line.trim_end();
+            let indent = content.len() - content.trim_start().len();
+            let trimmed = content.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with("//") {
+                continue;
+            }
+            let content_start = start + indent;
+            let content_end = start + content.len();
+            let (regex_end, name) = if trimmed.ends_with(';') {
+                (content_end - 1, None)
+            } else if trimmed.len() > 1 && trimmed.ends_with('"') {
+                // The name is the last quoted string on the line, preceded by whitespace.
+                let body = &content[..content.len() - 1];
+                match body.rfind('"') {
+                    Some(open) if open > indent => {
+                        let name = body[open + 1..].to_string();
+                        let span = Span::new(start + open + 1, content_end - 1);
+                        (start + open, Some((name, span)))
+                    }
+                    _ => (content_end, None),
+                }
+            } else {
+                (content_end, None)
+            };
+            let regex = src[content_start..regex_end].trim_end();
+            syntax.rules.push(LexRuleSyntax {
+                name,
+                regex: (
+                    regex.to_string(),
+                    Span::new(content_start, content_start + regex.len()),
+                ),
+                span: Span::new(content_start, content_end),
+            });
+        }
+        syntax
+    }
+
+    /// Returns the lexer rule named `name`.
+    pub fn rule(&self, name: &str) -> Option<&LexRuleSyntax> {
+        self.rules
+            .iter()
+            .find(|rule| rule.name.as_ref().map_or(false, |(n, _)| n == name))
+    }
+
+    /// Returns the named rule whose name contains `offset`.
+    pub fn rule_name_at(&self, offset: usize) -> Option<&LexRuleSyntax> {
+        self.rules.iter().find(|rule| {
+            rule.name.as_ref().map_or(false, |(_, span)| {
+                span.start() <= offset && offset <= span.end()
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(syntax: &YaccSyntax, idxs: &[usize]) -> Vec<String> {
+        idxs.iter()
+            .map(|idx| syntax.symbols[*idx].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let src = "%%\na: b c\n  | d\ne: f;\n";
+        let syntax = YaccSyntax::new(src);
+        assert_eq!(syntax.rules.len(), 2);
+        let a = &syntax.rules[0];
+        assert_eq!(a.name, "a");
+        assert_eq!(a.alts.len(), 2);
+        assert_eq!(names(&syntax, &a.alts[0].symbols), ["b", "c"]);
+        assert_eq!(names(&syntax, &a.alts[1].symbols), ["d"]);
+        assert_eq!(&src[a.span.start()..a.span.end()], "a: b c\n  | d");
+        assert_eq!(syntax.rules[1].name, "e");
+        assert_eq!(names(&syntax, &syntax.rules[1].alts[0].symbols), ["f"]);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let src = "%%\na: 'b\n  | c;\nd: e;\n";
+        let syntax = YaccSyntax::new(src);
+        assert_eq!(syntax.rules.len(), 2);
+        let a = &syntax.rules[0];
+        assert_eq!(names(&syntax, &a.alts[0].symbols), ["b"]);
+        let b = &syntax.symbols[a.alts[0].symbols[0]];
+        assert!(b.quoted);
+        assert_eq!(&src[b.name_span().start()..b.name_span().end()], "b");
+        assert_eq!(names(&syntax, &a.alts[1].symbols), ["c"]);
+        assert_eq!(syntax.rules[1].name, "d");
+
+        let src = "%%\na: 'x' '\n  | c;\n";
+        let syntax = YaccSyntax::new(src);
+        let a = &syntax.rules[0];
+        assert_eq!(names(&syntax, &a.alts[0].symbols), ["x", ""]);
+        let spans = a.alts[0]
+            .symbols
+            .iter()
+            .map(|&i| syntax.symbols[i].name_span())
+            .collect::<Vec<_>>();
+        assert_eq!(&src[spans[0].start()..spans[0].end()], "x");
+        assert_eq!(spans[1].start(), spans[1].end());
+        assert_eq!(spans[1].end(), src.find("'\n").unwrap() + 1);
+    }
+
+    #[test]
+    fn multibyte_escaped_char() {
+        let src = "%%\na: b { let c = '\\é'; '}' } c;\nd: e;\n";
+        let syntax = YaccSyntax::new(src);
+        assert_eq!(syntax.rules.len(), 2);
+        let a = &syntax.rules[0];
+        assert_eq!(names(&syntax, &a.alts[0].symbols), ["b", "c"]);
+        let action = syntax
+            .items
+            .iter()
+            .find(|item| item.kind == ItemKind::Action)
+            .unwrap();
+        assert_eq!(
+            &src[action.span.start()..action.span.end()],
+            "{ let c = '\\é'; '}' }"
+        );
+        assert_eq!(syntax.rules[1].name, "d");
+    }
+}
@@ -0,0 +1,749 @@
+//! Running the `[[tests]]` of a `nimbleparse.toml` against the current build of each parser.
+//!
+//! Every file in a test directory is parsed by the parser for its extension, files which no parser
+//! handles are ignored. Each input of an inputs file, or from a generator, is parsed as though it
+//! were its own file.
+//! With `pass = true` a test passes when its input parses without error, with `pass = false` it
+//! passes when parsing reports an error.
+//!
+//! Generator commands may be slow, so their inputs are cached by the server and only regenerated
+//! when the generator's configuration changes or a grammar is saved.
+use crate::diagnostics::{self, SOURCE};
+use crate::documents::byte_to_position;
+use crate::pretty;
+use crate::BuiltParser;
+use cfgrammar::Span;
+use nimbleparse_toml::{Generator, InputsFormat, TestInput, TomlInputs};
+use tower_lsp::lsp_types as lsp_ty;
+
+/// A test whose parse tree differs from its snapshot.
+pub const SNAPSHOT_MISMATCH: &str = "snapshot-mismatch";
+/// A test which is missing its snapshot.
+pub const SNAPSHOT_MISSING: &str = "snapshot-missing";
+
+/// The result of a single test.
+#[derive(Clone)]
+pub struct Outcome {
+    pub location: lsp_ty::Location,
+    pub passed: bool,
+}
+
+/// One of the inputs of a `TestKind::Inputs` file.
+pub struct Input {
+    pub text: String,
+    /// Where the input is in the file. When `verbatim` this is exactly `text`, otherwise the input
+    /// was escaped and this is its whole string, including the quotes.
+    pub span: Span,
+    pub verbatim: bool,
+    /// The input's own expectation, overriding that of its `[[tests]]` entry.
+    pub pass: Option<bool>,
+}
+
+impl Input {
+    /// Maps the span of an error in the input to a span in the file.
+    pub fn file_span(&self, span: Span) -> Span {
+        if self.verbatim {
+            Span::new(
+                self.span.start() + span.start(),
+                self.span.start() + span.end(),
+            )
+        } else {
+            self.span
+        }
+    }
+}
+
+fn split_delimited(src: &str, delimiter: &str) -> Vec<Input> {
+    let mut inputs = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut pass = None;
+    let mut push = |start: usize, end: usize, pass: Option<bool>| {
+        if src[start..end].trim().is_empty() {
+            return;
+        }
+        inputs.push(Input {
+            text: src[start..end].to_string(),
+            span: Span::new(start, end),
+            verbatim: true,
+            pass,
+        });
+    };
+    for line in src.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let expectation = match line.trim_end().strip_prefix(delimiter) {
+            Some("") => None,
+            Some(" pass") => Some(true),
+            Some(" fail") => Some(false),
+            _ => continue,
+        };
+        push(start, line_start, pass);
+        start = offset;
+        pass = expectation;
+    }
+    push(start, src.len(), pass);
+    inputs
+}
+
+/// Finds the next string value in `src` from `from`, which should hold `text`, so that error
+/// positions within it can be mapped directly into the file. Strings followed by `:` or `=` are
+/// keys, and are skipped, as are `#` comments when `comments` is set. Advances `from` past the
+/// string.
+///
+/// Returns the span of the string's contents if they are exactly `text`, otherwise the input
+/// needed escaping and the span includes the quotes.
+fn locate(src: &str, from: &mut usize, text: &str, comments: bool) -> Option<(Span, bool)> {
+    let bytes = src.as_bytes();
+    loop {
+        let pos = *from;
+        let open = pos + src[pos..].find(|c| c == '"' || c == '\'' || (comments && c == '#'))?;
+        if bytes[open] == b'#' {
+            *from = src[open..].find('\n').map_or(src.len(), |off| open + off);
+            continue;
+        }
+        let quote = &src[open..open + 1];
+        let triple = quote.repeat(3);
+        let (start, close) = if src[open..].starts_with(&triple) {
+            let start = open + 3;
+            (
+                start,
+                src[start..]
+                    .find(&triple)
+                    .map_or(src.len(), |off| start + off),
+            )
+        } else {
+            let mut close = open + 1;
+            while close < bytes.len() && bytes[close] != quote.as_bytes()[0] {
+                // Only basic strings have escapes, a quote is never escaped in a literal string.
+                if quote == "\"" && bytes[close] == b'\\' {
+                    close += 1;
+                }
+                close += 1;
+            }
+            (open + 1, close.min(src.len()))
+        };
+        let quote_len = start - open;
+        *from = (close + quote_len).min(src.len());
+        let after = src[*from..].trim_start();
+        if after.starts_with(':') || after.starts_with('=') {
+            continue;
+        }
+        return Some(if src[start..close] == *text {
+            (Span::new(start, close), true)
+        } else {
+            (Span::new(open, *from), false)
+        });
+    }
+}
+
+/// Splits the contents of a `TestKind::Inputs` file into its inputs.
+pub fn split_inputs(src: &str, format: &InputsFormat) -> Result<Vec<Input>, String> {
+    let inputs = match format {
+        InputsFormat::Delimiter(delimiter) => return Ok(split_delimited(src, delimiter)),
+        InputsFormat::Toml => {
+            toml::de::from_str::<TomlInputs>(src)
+                .map_err(|e| e.to_string())?
+                .inputs
+        }
+        InputsFormat::Json => {
+            serde_json::from_str::<Vec<TestInput>>(src).map_err(|e| e.to_string())?
+        }
+    };
+    let comments = *format == InputsFormat::Toml;
+    let mut from = 0;
+    let inputs = inputs
+        .into_iter()
+        .map(|input| {
+            let (text, pass) = match input {
+                TestInput::Input(text) => (text, None),
+                TestInput::Expect { input, pass } => (input, Some(pass)),
+            };
+            let (span, verbatim) =
+                locate(src, &mut from, &text, comments).unwrap_or((Span::new(from, from), false));
+            Input {
+                text,
+                span,
+                verbatim,
+                pass,
+            }
+        })
+        .collect();
+    Ok(inputs)
+}
+
+/// Every file under `dir`, recursively and in a stable order.
+pub fn files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Substitutes every combination of the values of `params` into `template`.
+fn expand_template(
+    template: &str,
+    params: &std::collections::BTreeMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut inputs = vec![template.to_string()];
+    for (name, values) in params {
+        let pattern = format!("{{{name}}}");
+        let pattern = pattern.as_str();
+        inputs = inputs
+            .iter()
+            .flat_map(|input| {
+                values
+                    .iter()
+                    .map(move |value| input.replace(pattern, value))
+            })
+            .collect();
+    }
+    inputs
+}
+
+/// How long a generator command may run before it is killed.
+const GENERATOR_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The inputs of a generator, each with its own expectation, or why it failed.
+pub type Generated = Result<Vec<(String, Option<bool>)>, String>;
+
+/// Runs a generator in `workspace_path`, returning each input and its own expectation.
+pub async fn generate(generator: &Generator, workspace_path: &std::path::Path) -> Generated {
+    match generator {
+        Generator::Command {
+            program,
+            args,
+            format,
+        } => {
+            let output = tokio::process::Command::new(program)
+                .args(args)
+                .current_dir(workspace_path)
+                .kill_on_drop(true)
+                .output();
+            let output = tokio::time::timeout(GENERATOR_TIMEOUT, output)
+                .await
+                .map_err(|_| {
+                    format!(
+                        "`{program}` was killed after running for {}s",
+                        GENERATOR_TIMEOUT.as_secs()
+                    )
+                })?
+                .map_err(|e| format!("Unable to run `{program}`: {e}"))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "`{program}` failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim_end()
+                ));
+            }
+            let stdout = String::from_utf8(output.stdout)
+                .map_err(|_| format!("The output of `{program}` isn't valid UTF-8"))?;
+            Ok(split_inputs(&stdout, format)?
+                .into_iter()
+                .map(|input| (input.text, input.pass))
+                .collect())
+        }
+        Generator::Template { template, params } => Ok(expand_template(template, params)
+            .into_iter()
+            .map(|input| (input, None))
+            .collect()),
+    }
+}
+
+fn error(range: lsp_ty::Range, message: String) -> lsp_ty::Diagnostic {
+    lsp_ty::Diagnostic {
+        range,
+        severity: Some(lsp_ty::DiagnosticSeverity::ERROR),
+        source: Some(SOURCE.to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Parses each input of a generator, returning a diagnostic at `location` for each failure
+/// which includes the generated text.
+pub fn run_generated(
+    parser: &BuiltParser,
+    generated: &Generated,
+    pass: bool,
+    location: &lsp_ty::Location,
+    outcomes: &mut Vec<Outcome>,
+) -> Vec<lsp_ty::Diagnostic> {
+    let inputs = match generated {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            outcomes.push(Outcome {
+                location: location.clone(),
+                passed: false,
+            });
+            return vec![error(location.range, e.clone())];
+        }
+    };
+    let mut diags = Vec::new();
+    for (text, input_pass) in inputs {
+        let errors = diagnostics::parse_errors(parser.lexerdef, parser.grm, parser.rtpb, text);
+        let expected = input_pass.unwrap_or(pass);
+        let passed = errors.is_empty() == expected;
+        if !passed {
+            let message = if expected {
+                let errors = errors
+                    .iter()
+                    .map(|(_, message)| message.as_str())
+                    .collect::<Vec<_>>();
+                format!(
+                    "Generated input failed to parse:\n{text}\n{}",
+                    errors.join("\n")
+                )
+            } else {
+                format!(
+                    "Expected a parse error, but this generated input parsed successfully:\n{text}"
+                )
+            };
+            diags.push(error(location.range, message));
+        }
+        outcomes.push(Outcome {
+            location: location.clone(),
+            passed,
+        });
+    }
+    diags
+}
+
+/// Turns the diagnostics of parsing a test input into the diagnostics of its test.
+///
+/// The errors of a test expected to fail are only hints, as they are what the test wants.
+/// A test expected to fail which parses is reported at `range`.
+pub fn test_diagnostics(
+    pass: bool,
+    mut diags: Vec<lsp_ty::Diagnostic>,
+    range: lsp_ty::Range,
+) -> (bool, Vec<lsp_ty::Diagnostic>) {
+    if pass {
+        (diags.is_empty(), diags)
+    } else if diags.is_empty() {
+        let message = "Expected a parse error, but this test parsed successfully".to_string();
+        (false, vec![error(range, message)])
+    } else {
+        for diag in &mut diags {
+            diag.severity = Some(lsp_ty::DiagnosticSeverity::HINT);
+        }
+        (true, diags)
+    }
+}
+
+/// The snapshot of the parse tree of the test `path`.
+pub fn snapshot_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut snapshot = path.as_os_str().to_owned();
+    snapshot.push(".tree");
+    std::path::PathBuf::from(snapshot)
+}
+
+pub fn is_snapshot(path: &std::path::Path) -> bool {
+    path.extension() == Some(std::ffi::OsStr::new("tree"))
+}
+
+/// A line based diff, with `-` before lines only in `expected` and `+` before lines only in
+/// `actual`. Only lines which differ are included, along with the line numbers of each hunk.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<_>>();
+    let new = actual.lines().collect::<Vec<_>>();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    // Longest common subsequence of what remains. When that would be too costly, every remaining
+    // line of `expected` is removed and every remaining line of `actual` added.
+    if old.len().saturating_mul(new.len()) > 1_000_000 {
+        let mut out = format!("@@ -{} +{} @@\n", prefix + 1, prefix + 1);
+        for line in old {
+            out.push_str(&format!("-{line}\n"));
+        }
+        for line in new {
+            out.push_str(&format!("+{line}\n"));
+        }
+        return out;
+    }
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    let mut in_hunk = false;
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            in_hunk = false;
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if !in_hunk {
+            out.push_str(&format!("@@ -{} +{} @@\n", prefix + i + 1, prefix + j + 1));
+            in_hunk = true;
+        }
+        if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Compares the parse tree of a test with its snapshot, returning a diagnostic if they differ.
+pub fn snapshot_diagnostic(
+    path: &std::path::Path,
+    tree: &str,
+    snapshot: Option<&str>,
+) -> Option<lsp_ty::Diagnostic> {
+    let snapshot_path = snapshot_path(path);
+    let name = snapshot_path.file_name()?.to_string_lossy();
+    let (code, message) = match snapshot {
+        Some(snapshot) if snapshot == tree => return None,
+        Some(snapshot) => (
+            SNAPSHOT_MISMATCH,
+            format!(
+                "The parse tree differs from its snapshot {name}:\n{}",
+                diff(snapshot, tree)
+            ),
+        ),
+        None => (SNAPSHOT_MISSING, format!("The snapshot {name} is missing")),
+    };
+    Some(lsp_ty::Diagnostic {
+        code: Some(lsp_ty::NumberOrString::String(code.to_string())),
+        related_information: lsp_ty::Url::from_file_path(&snapshot_path).ok().map(|uri| {
+            vec![lsp_ty::DiagnosticRelatedInformation {
+                location: lsp_ty::Location::new(uri, lsp_ty::Range::default()),
+                message: "snapshot".to_string(),
+            }]
+        }),
+        ..error(lsp_ty::Range::default(), message)
+    })
+}
+
+/// A quick fix rewriting the snapshot of the test `path` with its current parse tree,
+/// for any snapshot diagnostics among `diagnostics`.
+///
+/// With `create_files` the snapshot is created, or overwritten. Otherwise the client can only edit
+/// a snapshot which already exists, `snapshot` being its contents.
+pub fn snapshot_actions(
+    parser: &BuiltParser,
+    path: &std::path::Path,
+    rope: &ropey::Rope,
+    snapshot: Option<&ropey::Rope>,
+    create_files: bool,
+    diagnostics: &[lsp_ty::Diagnostic],
+) -> Vec<lsp_ty::CodeActionOrCommand> {
+    let diagnostics = diagnostics
+        .iter()
+        .filter(|diag| match &diag.code {
+            Some(lsp_ty::NumberOrString::String(code)) => {
+                code == SNAPSHOT_MISMATCH || code == SNAPSHOT_MISSING
+            }
+            _ => false,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    let uri = match lsp_ty::Url::from_file_path(snapshot_path(path)) {
+        Ok(uri) if !diagnostics.is_empty() => uri,
+        _ => return Vec::new(),
+    };
+    let tree = pretty::generic_tree(parser, rope);
+    let edit = if create_files {
+        // Creating with overwrite empties an existing snapshot before the tree is inserted.
+        lsp_ty::WorkspaceEdit {
+            document_changes: Some(lsp_ty::DocumentChanges::Operations(vec![
+                lsp_ty::DocumentChangeOperation::Op(lsp_ty::ResourceOp::Create(
+                    lsp_ty::CreateFile {
+                        uri: uri.clone(),
+                        options: Some(lsp_ty::CreateFileOptions {
+                            overwrite: Some(true),
+                            ignore_if_exists: None,
+                        }),
+                        annotation_id: None,
+                    },
+                )),
+                lsp_ty::DocumentChangeOperation::Edit(lsp_ty::TextDocumentEdit {
+                    text_document: lsp_ty::OptionalVersionedTextDocumentIdentifier {
+                        uri,
+                        version: None,
+                    },
+                    edits: vec![lsp_ty::OneOf::Left(lsp_ty::TextEdit {
+                        range: lsp_ty::Range::default(),
+                        new_text: tree,
+                    })],
+                }),
+            ])),
+            ..Default::default()
+        }
+    } else if let Some(snapshot) = snapshot {
+        let end = byte_to_position(snapshot, snapshot.len_bytes());
+        let mut changes = std::collections::HashMap::new();
+        changes.insert(
+            uri,
+            vec![lsp_ty::TextEdit {
+                range: lsp_ty::Range::new(lsp_ty::Position::new(0, 0), end),
+                new_text: tree,
+            }],
+        );
+        lsp_ty::WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }
+    } else {
+        // A missing snapshot is left to the `update_snapshot.cmd` command, which writes it to disk.
+        return Vec::new();
+    };
+    vec![lsp_ty::CodeActionOrCommand::CodeAction(
+        lsp_ty::CodeAction {
+            title: "Update the parse tree snapshot".to_string(),
+            kind: Some(lsp_ty::CodeActionKind::QUICKFIX),
+            diagnostics: Some(diagnostics),
+            edit: Some(edit),
+            is_preferred: Some(true),
+            ..Default::default()
+        },
+    )]
+}
+
+/// Converts a byte range of a `toml::Spanned` into an lsp range.
+pub fn toml_range(rope: &ropey::Rope, span: std::ops::Range<usize>) -> lsp_ty::Range {
+    lsp_ty::Range::new(
+        byte_to_position(rope, span.start),
+        byte_to_position(rope, span.end),
+    )
+}
+
+/// Summarises the outcomes of a `[[tests]]` entry, linking to each failed test.
+///
+/// `not_run` counts the tests whose parser doesn't currently build.
+pub fn summary(range: lsp_ty::Range, outcomes: &[Outcome], not_run: usize) -> lsp_ty::Diagnostic {
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| !outcome.passed)
+        .collect::<Vec<_>>();
+    let total = outcomes.len() + not_run;
+    let mut message = if failed.is_empty() {
+        format!("{} of {total} tests passed", outcomes.len())
+    } else {
+        format!("{} of {total} tests failed", failed.len())
+    };
+    if not_run > 0 {
+        message.push_str(&format!(
+            ", {not_run} not run because their parser doesn't build"
+        ));
+    }
+    let severity = if !failed.is_empty() {
+        lsp_ty::DiagnosticSeverity::ERROR
+    } else if not_run > 0 {
+        lsp_ty::DiagnosticSeverity::WARNING
+    } else {
+        lsp_ty::DiagnosticSeverity::INFORMATION
+    };
+    let related_information = failed
+        .iter()
+        .map(|outcome| lsp_ty::DiagnosticRelatedInformation {
+            location: outcome.location.clone(),
+            message: "failed".to_string(),
+        })
+        .collect::<Vec<_>>();
+    lsp_ty::Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some(SOURCE.to_string()),
+        message,
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spanned(src: &str, span: Span) -> &str {
+        &src[span.start()..span.end()]
+    }
+
+    #[test]
+    fn delimited() {
+        let src = "a\n%%\nb\n%%x\n%% fail\nc\n%% pass\n\n";
+        let inputs = split_delimited(src, "%%");
+        let texts = inputs
+            .iter()
+            .map(|input| input.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["a\n", "b\n%%x\n", "c\n"]);
+        let passes = inputs.iter().map(|input| input.pass).collect::<Vec<_>>();
+        assert_eq!(passes, [None, None, Some(false)]);
+        for input in &inputs {
+            assert!(input.verbatim);
+            assert_eq!(spanned(src, input.span), input.text);
+        }
+    }
+
+    #[test]
+    fn locate_skips_keys() {
+        let src = r#"[{"input": "a\"b", "pass": false}, "x"]"#;
+        let mut from = 0;
+        let (span, verbatim) = locate(src, &mut from, "a\"b", false).unwrap();
+        assert!(!verbatim);
+        assert_eq!(spanned(src, span), r#""a\"b""#);
+        let (span, verbatim) = locate(src, &mut from, "x", false).unwrap();
+        assert!(verbatim);
+        assert_eq!(spanned(src, span), "x");
+        assert_eq!(locate(src, &mut from, "", false), None);
+    }
+
+    #[test]
+    fn locate_triple_quoted() {
+        let src = r#"inputs = ["""a 'b'""", '''c "d"''', 'e']"#;
+        let mut from = 0;
+        for text in ["a 'b'", "c \"d\"", "e"] {
+            let (span, verbatim) = locate(src, &mut from, text, true).unwrap();
+            assert!(verbatim);
+            assert_eq!(spanned(src, span), text);
+        }
+    }
+
+    #[test]
+    fn locate_skips_comments() {
+        let src = "# \"a\" isn't an input\ninputs = [\n  \"b\", # 'a'\n  \"a\",\n]\n";
+        let mut from = 0;
+        let (span, _) = locate(src, &mut from, "b", true).unwrap();
+        assert_eq!(spanned(src, span), "b");
+        let (span, verbatim) = locate(src, &mut from, "a", true).unwrap();
+        assert!(verbatim);
+        assert_eq!(span.start(), src.rfind('a').unwrap());
+    }
+
+    #[test]
+    fn toml_inputs() {
+        let src = r#"# "x" is not an input
+inputs = [
+    "a",
+    { input = "b\n", pass = false }, # "a"
+    '''c''',
+]
+"#;
+        let inputs = split_inputs(src, &InputsFormat::Toml).unwrap();
+        let texts = inputs
+            .iter()
+            .map(|input| input.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["a", "b\n", "c"]);
+        let passes = inputs.iter().map(|input| input.pass).collect::<Vec<_>>();
+        assert_eq!(passes, [None, Some(false), None]);
+        assert!(inputs[0].verbatim);
+        assert_eq!(spanned(src, inputs[0].span), "a");
+        assert!(!inputs[1].verbatim);
+        assert_eq!(spanned(src, inputs[1].span), r#""b\n""#);
+        assert!(inputs[2].verbatim);
+        assert_eq!(spanned(src, inputs[2].span), "c");
+    }
+
+    #[test]
+    fn json_inputs() {
+        let src = r#"["a", {"input": "\u0062", "pass": true}]"#;
+        let inputs = split_inputs(src, &InputsFormat::Json).unwrap();
+        assert_eq!(inputs[0].text, "a");
+        assert!(inputs[0].verbatim);
+        assert_eq!(spanned(src, inputs[0].span), "a");
+        assert_eq!(inputs[1].text, "b");
+        assert_eq!(inputs[1].pass, Some(true));
+        assert!(!inputs[1].verbatim);
+        assert_eq!(spanned(src, inputs[1].span), r#""\u0062""#);
+    }
+
+    #[test]
+    fn diff_trims_common_lines() {
+        assert_eq!(diff("a\nb\nc\n", "a\nb\nc\n"), "");
+        assert_eq!(
+            diff("a\nb\nc\nd\n", "a\nx\nc\nd\n"),
+            "@@ -2 +2 @@\n-b\n+x\n"
+        );
+        assert_eq!(diff("a\nb\n", "a\nb\nc\n"), "@@ -3 +3 @@\n+c\n");
+    }
+
+    #[test]
+    fn diff_hunks() {
+        assert_eq!(
+            diff("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nE\n"),
+            "@@ -2 +2 @@\n-b\n+B\n@@ -5 +5 @@\n-e\n+E\n"
+        );
+        assert_eq!(
+            diff("a\nb\nc\n", "x\na\nc\n"),
+            "@@ -1 +1 @@\n+x\n@@ -2 +3 @@\n-b\n"
+        );
+    }
+
+    #[test]
+    fn diff_too_large() {
+        let lines = |prefix: &str| {
+            (0..=1000)
+                .map(|i| format!("{prefix}{i}\n"))
+                .collect::<String>()
+        };
+        let out = diff(
+            &format!("same\n{}", lines("a")),
+            &format!("same\n{}", lines("b")),
+        );
+        assert_eq!(out.matches("@@").count(), 2);
+        assert!(out.starts_with("@@ -2 +2 @@\n-a0\n-a1\n"));
+        assert!(out.contains("\n-a1000\n+b0\n"));
+        assert!(out.ends_with("\n+b1000\n"));
+    }
+
+    #[test]
+    fn snapshots() {
+        let path = std::path::Path::new("/tests/input.txt");
+        assert_eq!(snapshot_diagnostic(path, "tree\n", Some("tree\n")), None);
+
+        let code = |diag: &lsp_ty::Diagnostic| match &diag.code {
+            Some(lsp_ty::NumberOrString::String(code)) => code.clone(),
+            _ => String::new(),
+        };
+        let missing = snapshot_diagnostic(path, "tree\n", None).unwrap();
+        assert_eq!(code(&missing), SNAPSHOT_MISSING);
+        assert_eq!(missing.message, "The snapshot input.txt.tree is missing");
+        let related = missing.related_information.unwrap();
+        assert_eq!(related[0].location.uri.path(), "/tests/input.txt.tree");
+
+        let mismatch = snapshot_diagnostic(path, "a\nc\n", Some("a\nb\n")).unwrap();
+        assert_eq!(code(&mismatch), SNAPSHOT_MISMATCH);
+        assert_eq!(
+            mismatch.message,
+            "The parse tree differs from its snapshot input.txt.tree:\n@@ -2 +2 @@\n-b\n+c\n"
+        );
+    }
+}
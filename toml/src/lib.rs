@@ -31,19 +31,82 @@ fn default_recovery_kind() -> RecoveryKind {
     RecoveryKind::CPCTPlus
 }
 
-// A couple of more TestKinds we could consider in the future
-// are source generators, and files which contain a list of strings each to be parsed as
-// it's own file... So the reason TestKind is like this is for future expansion.
+// The reason TestKind is like this is for future expansion.
 // We should consider having a trait for it...
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TestKind {
+    /// A directory relative to the workspace, each file of which is parsed as its own input.
     Dir(String),
+    /// A file relative to the workspace containing many inputs, each parsed as its own file
+    /// by the parser for `extension`.
+    Inputs {
+        file: String,
+        extension: String,
+        format: InputsFormat,
+    },
+    /// Inputs produced by a generator, each parsed as its own file by the parser for `extension`.
+    Generated {
+        extension: String,
+        generator: Generator,
+    },
+}
+
+/// A source of generated test inputs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Generator {
+    /// A command run in the workspace directory, whose output is split into inputs.
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        format: InputsFormat,
+    },
+    /// A template in which each `{param}` is replaced by one of its values, giving an input
+    /// for every combination of values.
+    Template {
+        template: String,
+        params: std::collections::BTreeMap<String, Vec<String>>,
+    },
+}
+
+/// How the inputs of a `TestKind::Inputs` file are laid out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum InputsFormat {
+    /// Inputs separated by lines containing just the delimiter, blank inputs are skipped.
+    ///
+    /// A delimiter line may be followed by ` pass` or ` fail`, giving the input after it
+    /// its own expectation.
+    Delimiter(String),
+    /// A TOML document with an `inputs` array of `TestInput`s.
+    Toml,
+    /// A JSON array of `TestInput`s.
+    Json,
+}
+
+/// An input of a `TestKind::Inputs` file in the `Toml` or `Json` formats.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum TestInput {
+    /// An input with the expectation of its `[[tests]]` entry.
+    Input(String),
+    /// An input with its own expectation.
+    Expect { input: String, pass: bool },
+}
+
+/// The contents of a `TestKind::Inputs` file in the `Toml` format.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TomlInputs {
+    pub inputs: Vec<TestInput>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TestDir {
     pub kind: toml::Spanned<TestKind>,
     pub pass: bool,
+    /// For `TestKind::Dir`, whether each input has its expected parse tree in a file beside it,
+    /// named after the input with `.tree` appended.
+    #[serde(default)]
+    pub snapshots: bool,
 }
 
 #[cfg(test)]
@@ -53,4 +116,97 @@ mod tests {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn inputs_test_kind() {
+        let workspace: super::Workspace = toml::de::from_str(
+            r#"
+            parsers = [{ l_file = "calc.l", y_file = "calc.y", extension = ".calc" }]
+
+            [[tests]]
+            kind = { Dir = "tests/pass" }
+            pass = true
+
+            [[tests]]
+            kind = { Inputs = { file = "tests/exprs.txt", extension = ".calc", format = { Delimiter = "---" } } }
+            pass = true
+
+            [[tests]]
+            kind = { Inputs = { file = "tests/exprs.json", extension = ".calc", format = "Json" } }
+            pass = false
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            workspace.tests[0].kind.get_ref(),
+            super::TestKind::Dir(_)
+        ));
+        match workspace.tests[1].kind.get_ref() {
+            super::TestKind::Inputs { format, .. } => {
+                assert_eq!(*format, super::InputsFormat::Delimiter("---".to_string()))
+            }
+            kind => panic!("unexpected test kind {kind:?}"),
+        }
+        match workspace.tests[2].kind.get_ref() {
+            super::TestKind::Inputs { format, .. } => {
+                assert_eq!(*format, super::InputsFormat::Json)
+            }
+            kind => panic!("unexpected test kind {kind:?}"),
+        }
+
+        let inputs: Vec<super::TestInput> =
+            serde_json::from_str(r#"["1 + 2", { "input": "1 +", "pass": false }]"#).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                super::TestInput::Input("1 + 2".to_string()),
+                super::TestInput::Expect {
+                    input: "1 +".to_string(),
+                    pass: false
+                }
+            ]
+        );
+        let inputs: super::TomlInputs =
+            toml::de::from_str(r#"inputs = ["1 + 2", { input = "1 +", pass = false }]"#).unwrap();
+        assert_eq!(inputs.inputs.len(), 2);
+    }
+
+    #[test]
+    fn generated_test_kind() {
+        let test: super::TestDir = toml::de::from_str(
+            r#"
+            kind = { Generated = { extension = ".calc", generator = { Template = { template = "1 {op} 2", params = { op = ["+", "*"] } } } } }
+            pass = true
+            "#,
+        )
+        .unwrap();
+        match test.kind.get_ref() {
+            super::TestKind::Generated {
+                generator: super::Generator::Template { params, .. },
+                ..
+            } => assert_eq!(params["op"], vec!["+", "*"]),
+            kind => panic!("unexpected test kind {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn snapshots_default_off() {
+        let workspace: super::Workspace = toml::de::from_str(
+            r#"
+            parsers = [{ l_file = "calc.l", y_file = "calc.y", extension = ".calc" }]
+
+            [[tests]]
+            kind = { Dir = "tests/pass" }
+            pass = true
+
+            [[tests]]
+            kind = { Dir = "tests/trees" }
+            pass = true
+            snapshots = true
+            "#,
+        )
+        .unwrap();
+        assert!(!workspace.tests[0].snapshots);
+        assert!(workspace.tests[1].snapshots);
+    }
 }